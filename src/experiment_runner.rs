@@ -0,0 +1,340 @@
+use crate::model::{
+    experiment::{set_pointer, Experiment},
+    scenario::Scenario,
+    state::state::ModelState,
+};
+use crate::system_interface::{
+    object_grid_loader::read_raster,
+    output_writer::{output_path, write_csv},
+};
+
+use anyhow::{anyhow, Error};
+use krabmaga::engine::{schedule::Schedule, state::State};
+use krabmaga::rayon::{prelude::*, ThreadPoolBuilder};
+use krabmaga::{
+    build_configurations, build_dataframe, explore_parallel, simulate_explore, DataFrame,
+    ExploreMode,
+};
+use ndarray::Array2;
+use serde_json::{Map, Value};
+use std::any::Any;
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::BufReader;
+use std::path::Path;
+use std::sync::{Mutex, RwLock};
+use std::thread;
+
+const RESULT_COLUMNS: &str = "repetition,seed,steps,trips,arrived,mean_travel_time,median_travel_time,p90_travel_time,mean_delay,mean_replans,evacuation_time";
+
+//Everything needed to start one run of the experiment
+struct RunSettings {
+    combination: usize,
+    repetition: u32,
+    seed: u64,
+    input: String,
+    num_agents: u32,
+    steps: u64,
+    scenario: Scenario,
+}
+
+fn value_text(value: &Value) -> String {
+    match value {
+        Value::String(text) => text.clone(),
+        other => other.to_string(),
+    }
+}
+
+//Address with its port moved up by `offset`
+fn offset_port(address: &str, offset: usize) -> Result<String, Error> {
+    let (host, port) = address
+        .rsplit_once(':')
+        .ok_or_else(|| anyhow!("Stream address {} has no port", address))?;
+    let port = port
+        .parse::<usize>()
+        .ok()
+        .map(|port| port + offset)
+        .filter(|port| *port <= u16::MAX as usize)
+        .ok_or_else(|| {
+            anyhow!(
+                "Stream address {} has no room for {} runs",
+                address,
+                offset + 1
+            )
+        })?;
+    Ok(format!("{}:{}", host, port))
+}
+
+//Apply one combination of parameter values on top of the experiment's base settings
+fn run_settings(
+    experiment: &Experiment,
+    base_scenario: &Value,
+    combination: usize,
+    values: &[Value],
+    repetition: u32,
+) -> Result<RunSettings, Error> {
+    let (mut input, mut num_agents, mut steps) = (
+        experiment.input.clone(),
+        experiment.num_agents,
+        experiment.steps,
+    );
+    let mut scenario_value = base_scenario.clone();
+
+    for (parameter, value) in experiment.parameters.iter().zip(values) {
+        let whole_number = || {
+            value
+                .as_u64()
+                .ok_or_else(|| anyhow!("{} takes whole numbers, not {}", parameter.name, value))
+        };
+        match parameter.name.as_str() {
+            "num_agents" => num_agents = whole_number()? as u32,
+            "steps" => steps = whole_number()?,
+            "input" => input = value_text(value),
+            pointer => set_pointer(&mut scenario_value, pointer, value.clone())?,
+        }
+    }
+
+    let seed = experiment.seed + repetition as u64;
+    let mut scenario: Scenario = serde_json::from_value(scenario_value)?;
+    scenario.seed = Some(seed);
+    scenario.output_dir = Path::new(&experiment.output_dir)
+        .join(format!("run_{}_{}", combination, repetition))
+        .to_string_lossy()
+        .to_string();
+    //Runs side by side cannot share a port, so each streams on the base port plus its number
+    if let Some(stream) = scenario.stream.as_mut() {
+        let run_idx = combination * experiment.repetitions as usize + repetition as usize;
+        stream.address = offset_port(&stream.address, run_idx)?;
+    }
+
+    Ok(RunSettings {
+        combination,
+        repetition,
+        seed,
+        input,
+        num_agents,
+        steps,
+        scenario,
+    })
+}
+
+//Result columns of a finished run
+fn result_row(repetition: u32, seed: u64, state: &ModelState) -> String {
+    let summary = state.trip_summaries.last().cloned().unwrap_or_default();
+    let evacuation_time = match state.evacuated.iter().map(|(_, _, step)| step).max() {
+        Some(last_step) if state.evacuation_complete() => {
+            ((last_step + 1) as f32 * state.step_duration).to_string()
+        }
+        _ => String::new(),
+    };
+    format!(
+        "{},{},{},{},{},{},{},{},{},{},{}",
+        repetition,
+        seed,
+        state.step,
+        summary.trips,
+        summary.arrived,
+        summary.mean_travel_time,
+        summary.median_travel_time,
+        summary.p90_travel_time,
+        summary.mean_delay,
+        summary.mean_replans,
+        evacuation_time
+    )
+}
+
+//Runs of the experiment being explored, with the rasters they read. `explore!` builds every
+//state from its inputs alone, so the runs are looked up here by number.
+static EXPLORED_RUNS: RwLock<Vec<(RunSettings, Option<Array2<u8>>)>> = RwLock::new(Vec::new());
+//Held for a whole exploration, so experiments started side by side take turns
+static EXPLORATION: Mutex<()> = Mutex::new(());
+
+/// One run of an experiment, built and stepped by `explore!`. The model state is stepped
+/// through it, and `row` holds the run's result columns once it has ended.
+struct ExperimentRun {
+    run: usize,
+    row: String,
+    repetition: u32,
+    seed: u64,
+    state: ModelState,
+}
+
+impl ExperimentRun {
+    fn new(run: usize) -> ExperimentRun {
+        let runs = EXPLORED_RUNS
+            .read()
+            .unwrap_or_else(|poisoned| poisoned.into_inner());
+        let (settings, grid) = &runs[run];
+        let dim = match grid {
+            Some(grid) => (grid.ncols() as f32, grid.nrows() as f32),
+            None => (400., 400.),
+        };
+        ExperimentRun {
+            run,
+            row: String::new(),
+            repetition: settings.repetition,
+            seed: settings.seed,
+            state: ModelState::new(
+                dim,
+                settings.num_agents,
+                settings.steps,
+                grid.clone(),
+                settings.scenario.clone(),
+            ),
+        }
+    }
+}
+
+impl State for ExperimentRun {
+    fn update(&mut self, step: u64) {
+        self.state.update(step);
+    }
+
+    fn reset(&mut self) {
+        self.state.reset();
+    }
+
+    fn init(&mut self, schedule: &mut Schedule) {
+        self.state.init(schedule);
+    }
+
+    //Pedestrians downcast to the model state, not to the run around it
+    fn as_any(&self) -> &dyn Any {
+        self.state.as_any()
+    }
+
+    fn as_any_mut(&mut self) -> &mut dyn Any {
+        self.state.as_any_mut()
+    }
+
+    fn as_state_mut(&mut self) -> &mut dyn State {
+        self
+    }
+
+    fn as_state(&self) -> &dyn State {
+        self
+    }
+
+    fn before_step(&mut self, schedule: &mut Schedule) {
+        self.state.before_step(schedule);
+    }
+
+    fn after_step(&mut self, schedule: &mut Schedule) {
+        self.state.after_step(schedule);
+    }
+
+    fn end_condition(&mut self, schedule: &mut Schedule) -> bool {
+        let ended = self.state.end_condition(schedule);
+        if ended {
+            self.row = result_row(self.repetition, self.seed, &self.state);
+        }
+        ended
+    }
+}
+
+/// Run every combination of the experiment's parameters with its repetitions through
+/// krABMaga's parallel model exploration, on `threads` threads, and write one row per run to
+/// `experiment_results.csv`
+pub fn run_experiment(experiment: &Experiment) -> Result<(), Error> {
+    let base_scenario = match &experiment.scenario {
+        Some(scenario_file) => serde_json::from_reader(BufReader::new(File::open(scenario_file)?))?,
+        None => Value::Object(Map::new()),
+    };
+
+    let combinations = experiment.combinations()?;
+    let mut runs = Vec::<RunSettings>::new();
+    for (combination, values) in combinations.iter().enumerate() {
+        for repetition in 0..experiment.repetitions {
+            runs.push(run_settings(
+                experiment,
+                &base_scenario,
+                combination,
+                values,
+                repetition,
+            )?);
+        }
+    }
+
+    //Each raster is read once and shared by the runs using it
+    let mut grids = HashMap::<String, Option<Array2<u8>>>::new();
+    for run in runs.iter() {
+        if !grids.contains_key(&run.input) {
+            let grid = match run.input.is_empty() {
+                true => None,
+                false => Some(read_raster(run.input.clone())?),
+            };
+            grids.insert(run.input.clone(), grid);
+        }
+    }
+
+    let threads = experiment
+        .threads
+        .or_else(|| thread::available_parallelism().ok().map(|n| n.get()))
+        .unwrap_or(1)
+        .clamp(1, runs.len().max(1));
+    println!(
+        "Running {} combinations x {} repetitions on {} threads",
+        combinations.len(),
+        experiment.repetitions,
+        threads
+    );
+
+    //Every run ends at its own step count, or earlier once its evacuation is complete
+    let max_steps = runs.iter().map(|run| run.steps).max().unwrap_or(0) as u32;
+    let run_count = runs.len();
+    let combination_of: Vec<usize> = runs.iter().map(|run| run.combination).collect();
+    let pool = ThreadPoolBuilder::new().num_threads(threads).build()?;
+
+    let _exploration = EXPLORATION
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner());
+    *EXPLORED_RUNS
+        .write()
+        .unwrap_or_else(|poisoned| poisoned.into_inner()) = runs
+        .into_iter()
+        .map(|run| {
+            let grid = grids[&run.input].clone();
+            (run, grid)
+        })
+        .collect();
+    //Each repetition is a run of its own, since it needs its own seed
+    let run: Vec<usize> = (0..run_count).collect();
+    let repetitions_per_run = 1;
+    let results = pool.install(|| {
+        explore_parallel!(
+            max_steps,
+            repetitions_per_run,
+            ExperimentRun,
+            input { run: usize },
+            output [row: String],
+            ExploreMode::Matched,
+        )
+    });
+    EXPLORED_RUNS
+        .write()
+        .unwrap_or_else(|poisoned| poisoned.into_inner())
+        .clear();
+
+    let header = experiment
+        .parameters
+        .iter()
+        .map(|parameter| parameter.name.clone())
+        .chain([RESULT_COLUMNS.to_string()])
+        .collect::<Vec<String>>()
+        .join(",");
+    let rows = results.into_iter().map(|result| {
+        let (run_idx, row) = (result.run, result.row);
+        let values: Vec<String> = combinations[combination_of[run_idx]]
+            .iter()
+            .map(value_text)
+            .collect();
+        match values.is_empty() {
+            true => row,
+            false => format!("{},{}", values.join(","), row),
+        }
+    });
+    let path = output_path(&experiment.output_dir, "experiment_results.csv")?;
+    write_csv(&path, &header, rows)?;
+    println!("Experiment results written to {}", path.display());
+    Ok(())
+}
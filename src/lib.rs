@@ -0,0 +1,39 @@
+//! Grid-based pedestrian simulation on krABMaga.
+//!
+//! Build a [`Scenario`] (or read one with [`read_scenario`]), load the obstacle raster with
+//! [`load_obstacle_grid`], create a [`ModelState`] and step it with a [`Simulation`].
+//! Results are written to the scenario's output directory at the end of each run, and the
+//! per-run statistics stay available on the state, e.g. `ModelState::trip_summaries`.
+
+pub mod experiment_runner;
+pub mod model;
+pub mod server;
+pub mod simulation;
+pub mod system_interface;
+
+#[cfg(feature = "python")]
+pub mod python;
+#[cfg(any(feature = "visualization", feature = "visualization_wasm"))]
+pub mod visualization;
+
+pub static DISCRETIZATION: f32 = 10.0 / 1.5;
+pub static TOROIDAL: bool = false;
+
+pub use experiment_runner::run_experiment;
+pub use model::{
+    calc_utils::pathfinding::astar_int2d,
+    experiment::Experiment,
+    pedestrian::{PedStatus, Pedestrian},
+    scenario::Scenario,
+    snapshot::Snapshot,
+    state::state::ModelState,
+    trip::{TripRecord, TripSummary},
+};
+pub use server::serve;
+pub use simulation::Simulation;
+pub use system_interface::{
+    experiment_loader::read_experiment,
+    object_grid_loader::{load_obstacle_grid, read_raster},
+    scenario_loader::read_scenario,
+    snapshot_io::{read_snapshot, write_snapshot},
+};
@@ -1,94 +1,123 @@
 // Global imports (needed for the simulation to run)
-use crate::model::state::state::ModelState;
-mod model;
-mod system_interface;
-
-use clap::Parser;
+use clap::{Parser, Subcommand};
+use pedestrian_sim::{load_obstacle_grid, read_scenario, read_snapshot, ModelState, Scenario};
 use std::error::Error;
-use system_interface::object_grid_loader::read_raster;
 
 #[cfg(not(any(feature = "visualization", feature = "visualization_wasm")))]
-use krabmaga::*;
+use {
+    krabmaga::*,
+    pedestrian_sim::{read_experiment, run_experiment, serve},
+};
 
 // Visualization specific imports
 #[cfg(any(feature = "visualization", feature = "visualization_wasm"))]
 use {
-    crate::visualization::model_vis::ModelVis, krabmaga::bevy::prelude::Color,
-    krabmaga::visualization::visualization::Visualization,
+    krabmaga::bevy::prelude::Color, krabmaga::visualization::visualization::Visualization,
+    pedestrian_sim::visualization::model_vis::ModelVis,
 };
 
-#[cfg(any(feature = "visualization", feature = "visualization_wasm"))]
-mod visualization;
-
-pub static DISCRETIZATION: f32 = 10.0 / 1.5;
-pub static TOROIDAL: bool = false;
-
 #[derive(Parser, Debug)]
 struct Args {
-    /// Raster file to read in as obstacle grid
-    #[arg(short, long)]
+    #[command(subcommand)]
+    command: Option<Command>,
+
+    /// Raster file to read in as obstacle grid; an open 400 x 400 grid if left out
+    #[arg(short, long, default_value = "")]
     input: String,
+
+    /// JSON scenario file with scheduled obstacle changes and other run settings
+    #[arg(short, long)]
+    scenario: Option<String>,
+
+    /// Directory for run outputs, overriding the scenario's output_dir
+    #[arg(short, long)]
+    output_dir: Option<String>,
+
+    /// Snapshot file to start every run from instead of a new population
+    #[arg(short, long)]
+    restore: Option<String>,
+
+    /// JSON experiment file with parameter ranges to sweep instead of a single simulation
+    #[arg(short, long)]
+    experiment: Option<String>,
+
+    /// Number of pedestrians (2 without visualization, 500 with it)
+    #[arg(short, long)]
+    num_agents: Option<u32>,
+
+    /// Steps per run
+    #[arg(long, default_value_t = 100)]
+    steps: u64,
+
+    /// Number of runs
+    #[arg(long, default_value_t = 10)]
+    repetitions: u64,
+}
+
+#[derive(Subcommand, Debug)]
+enum Command {
+    /// Serve a local HTTP/JSON API to submit scenarios and control their runs
+    Serve {
+        /// Address to listen on
+        #[arg(long, default_value = "127.0.0.1:8080")]
+        address: String,
+    },
+}
+
+//Obstacle grid, scenario and initial state as given on the command line
+fn build_state(
+    args: Args,
+    default_agents: u32,
+    num_steps: u64,
+) -> Result<ModelState, Box<dyn Error>> {
+    let (dim, obj_grid) = load_obstacle_grid(&args.input)?;
+
+    let mut scenario = match args.scenario {
+        Some(scenario_file) => read_scenario(scenario_file)?,
+        None => Scenario::default(),
+    };
+    if let Some(output_dir) = args.output_dir {
+        scenario.output_dir = output_dir;
+    }
+
+    let num_agents = args.num_agents.unwrap_or(default_agents);
+    let mut state = ModelState::new(dim, num_agents, num_steps, obj_grid, scenario);
+    if let Some(snapshot_file) = args.restore {
+        state.restore_snapshot(read_snapshot(snapshot_file)?)?;
+    }
+    Ok(state)
 }
 
 // Main used when only the simulation should run, without any visualization.
 #[cfg(not(any(feature = "visualization", feature = "visualization_wasm")))]
-fn main() -> Result<(), image::ImageError> {
+fn main() -> Result<(), Box<dyn Error>> {
     let args = Args::parse();
-    let dim: (f32, f32);
-
-    let obj_grid = match args.input.is_empty() {
-        true => {
-            dim = (400., 400.);
-            None
-        }
-        false => match read_raster(args.input) {
-            Ok(grid) => {
-                dim = (grid.ncols() as f32, grid.nrows() as f32);
-                Some(grid)
-            }
-            Err(e) => {
-                return Err(e);
-            }
-        },
-    };
-
-    let step = 100;
+    if let Some(Command::Serve { address }) = &args.command {
+        let output_dir = args.output_dir.as_deref().unwrap_or("output");
+        serve(address, output_dir)?;
+        return Ok(());
+    }
+    if let Some(experiment_file) = args.experiment {
+        run_experiment(&read_experiment(experiment_file)?)?;
+        return Ok(());
+    }
 
-    let num_agents = 2;
+    let (step, repetitions) = (args.steps, args.repetitions);
+    let state = build_state(args, 2, step)?;
 
-    //let (agents, paths)
-
-    let state = ModelState::new(dim, num_agents, obj_grid);
-
-    simulate!(state, step, 10);
+    simulate!(state, step, repetitions);
 
     Ok(())
 }
 
 // Main used when a visualization feature is applied.
 #[cfg(any(feature = "visualization", feature = "visualization_wasm"))]
-fn main() -> Result<(), image::ImageError> {
+fn main() -> Result<(), Box<dyn Error>> {
     let args = Args::parse();
-    let dim: (f32, f32);
     // Initialize the simulation and its visualization here.
-    let obj_grid = match args.input.is_empty() {
-        true => {
-            dim = (400., 400.);
-            None
-        }
-        false => match read_raster(args.input) {
-            Ok(grid) => {
-                dim = (grid.ncols() as f32, grid.nrows() as f32);
-                Some(grid)
-            }
-            Err(e) => {
-                return Err(e);
-            }
-        },
-    };
-    let num_agents = 500;
+    let state = build_state(args, 500, u64::MAX)?;
+    let dim = state.dim;
 
-    let state = ModelState::new(dim, num_agents, obj_grid);
     Visualization::default()
         .with_window_dimensions(1280., 720.)
         .with_simulation_dimensions(dim.0, dim.1)
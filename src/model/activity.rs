@@ -0,0 +1,85 @@
+use crate::model::{
+    profile::Spread,
+    scenario::Region,
+    snapshot::{from_point, to_point},
+};
+
+use krabmaga::engine::location::Real2D;
+use serde::{Deserialize, Serialize};
+
+/// Place where pedestrians stop on their way, such as a shop, a bench or a bus stop. Each
+/// pedestrian stops at a free cell of the region and stays there for `dwell` seconds.
+#[derive(Clone, Debug, Deserialize)]
+pub struct Stop {
+    #[serde(default)]
+    pub name: String,
+    pub region: Region,
+    #[serde(default = "default_dwell")]
+    pub dwell: Spread,
+}
+
+fn default_dwell() -> Spread {
+    Spread {
+        mean: 0.,
+        sd: 0.,
+        min: None,
+        max: None,
+    }
+}
+
+/// Stops that `share` of the population visits in order, before walking on to its own destination
+#[derive(Clone, Debug, Deserialize)]
+pub struct ActivityChain {
+    pub name: String,
+    pub share: f32,
+    pub stops: Vec<Stop>,
+}
+
+/// Index of the chain for a uniform draw from [0, 1), or None for pedestrians walking straight
+/// to their destination. Shares are fractions of the population, the rest has no chain.
+pub fn choose_chain(chains: &[ActivityChain], draw: f32) -> Option<usize> {
+    let mut cumulative = 0.;
+    for (idx, chain) in chains.iter().enumerate() {
+        cumulative += chain.share.max(0.);
+        if draw < cumulative {
+            return Some(idx);
+        }
+    }
+    None
+}
+
+/// Part of an itinerary: where it ends and the steps spent there before the next leg
+#[derive(Clone, Copy, Debug, Serialize, Deserialize)]
+pub struct Leg {
+    pub dest: [f32; 2],
+    pub dwell_steps: u64,
+}
+
+impl Leg {
+    pub fn new(dest: Real2D, dwell_steps: u64) -> Leg {
+        Leg {
+            dest: to_point(&dest),
+            dwell_steps,
+        }
+    }
+
+    pub fn dest(&self) -> Real2D {
+        from_point(&self.dest)
+    }
+}
+
+/// Legs of a pedestrian's trip, the last one ending at its own destination
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Itinerary {
+    pub chain: usize,
+    pub legs: Vec<Leg>,
+    //Index of the leg being walked
+    pub leg: usize,
+}
+
+impl Itinerary {
+    /// The leg being walked and the one after it, or None on the last leg
+    pub fn next_legs(&self) -> Option<(Leg, Leg)> {
+        Some((*self.legs.get(self.leg)?, *self.legs.get(self.leg + 1)?))
+    }
+}
@@ -0,0 +1,97 @@
+use krabmaga::engine::location::Real2D;
+use ndarray::Array2;
+use serde::Deserialize;
+use std::f32::consts::PI;
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum DensityMethod {
+    Kernel,
+    Voronoi,
+}
+
+//Gaussian kernel density estimate in persons/m², with bandwidth in metres,
+//on a (height, width) grid of cells of cell_size metres
+pub fn kernel_density(
+    positions: &[Real2D],
+    dim: (usize, usize),
+    cell_size: f32,
+    bandwidth: f32,
+) -> Array2<f32> {
+    let (height, width) = dim;
+    let mut density = Array2::<f32>::zeros(dim);
+    let reach = (3. * bandwidth / cell_size).ceil() as i32;
+    let norm = 1. / (2. * PI * bandwidth.powi(2));
+
+    for pos in positions {
+        let (col, row) = (pos.x as i32, pos.y as i32);
+        for y in (row - reach).max(0)..=(row + reach).min(height as i32 - 1) {
+            for x in (col - reach).max(0)..=(col + reach).min(width as i32 - 1) {
+                let dx = (x as f32 - pos.x) * cell_size;
+                let dy = (y as f32 - pos.y) * cell_size;
+                density[[y as usize, x as usize]] +=
+                    norm * (-(dx * dx + dy * dy) / (2. * bandwidth.powi(2))).exp();
+            }
+        }
+    }
+    density
+}
+
+//Nearest pedestrian of every cell within max_radius metres of it, which discretises the
+//pedestrians' Voronoi cells on the grid
+pub fn voronoi_owners(
+    positions: &[Real2D],
+    dim: (usize, usize),
+    cell_size: f32,
+    max_radius: f32,
+) -> Array2<Option<usize>> {
+    let (height, width) = dim;
+    let mut nearest = Array2::<Option<usize>>::from_elem(dim, None);
+    let mut nearest_dist = Array2::<f32>::from_elem(dim, f32::MAX);
+    let reach = (max_radius / cell_size).ceil() as i32;
+
+    for (ped_idx, pos) in positions.iter().enumerate() {
+        let (col, row) = (pos.x as i32, pos.y as i32);
+        for y in (row - reach).max(0)..=(row + reach).min(height as i32 - 1) {
+            for x in (col - reach).max(0)..=(col + reach).min(width as i32 - 1) {
+                let dx = (x as f32 - pos.x) * cell_size;
+                let dy = (y as f32 - pos.y) * cell_size;
+                let dist = (dx * dx + dy * dy).sqrt();
+                let idx = [y as usize, x as usize];
+                if dist <= max_radius && dist < nearest_dist[idx] {
+                    nearest_dist[idx] = dist;
+                    nearest[idx] = Some(ped_idx);
+                }
+            }
+        }
+    }
+    nearest
+}
+
+//Number of cells in the Voronoi cell of each pedestrian
+pub fn voronoi_cell_counts(owners: &Array2<Option<usize>>, num_peds: usize) -> Vec<u32> {
+    let mut cell_counts = vec![0_u32; num_peds];
+    owners
+        .iter()
+        .flatten()
+        .for_each(|ped_idx| cell_counts[*ped_idx] += 1);
+    cell_counts
+}
+
+//Voronoi density in persons/m²: each cell takes 1 / (area of the Voronoi cell of the nearest
+//pedestrian). Voronoi cells are bounded to max_radius metres around each pedestrian.
+pub fn voronoi_density(
+    positions: &[Real2D],
+    dim: (usize, usize),
+    cell_size: f32,
+    max_radius: f32,
+) -> Array2<f32> {
+    let owners = voronoi_owners(positions, dim, cell_size, max_radius);
+    let cell_counts = voronoi_cell_counts(&owners, positions.len());
+
+    let cell_area = cell_size * cell_size;
+    owners.mapv(|owner| match owner {
+        Some(ped_idx) => 1. / (cell_counts[ped_idx] as f32 * cell_area),
+        None => 0.,
+    })
+}
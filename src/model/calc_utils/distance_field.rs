@@ -0,0 +1,119 @@
+use krabmaga::engine::fields::sparse_number_grid_2d::SparseNumberGrid2D;
+use krabmaga::engine::location::Int2D;
+use ndarray::Array2;
+use std::collections::VecDeque;
+
+pub const UNREACHABLE: u32 = u32::MAX;
+
+fn neighbors(node: &Int2D, width: i32, height: i32) -> Vec<Int2D> {
+    //Four-directional moves, as in astar_int2d
+    [(-1, 0), (1, 0), (0, -1), (0, 1)]
+        .iter()
+        .map(|(dx, dy)| Int2D {
+            x: node.x + dx,
+            y: node.y + dy,
+        })
+        .filter(|n| n.x >= 0 && n.y >= 0 && n.x < width && n.y < height)
+        .collect()
+}
+
+//Number of steps from every free cell to the nearest source cell over the obstacle grid,
+//indexed [[row, col]] like the raster. Cells that cannot reach a source hold UNREACHABLE.
+//`forbids(from, to)` rules out single moves, such as against a one-way door.
+pub fn distance_field<I>(
+    sources: I,
+    grid: &SparseNumberGrid2D<u8>,
+    forbids: &dyn Fn(&Int2D, &Int2D) -> bool,
+) -> Array2<u32>
+where
+    I: IntoIterator<Item = Int2D>,
+{
+    let (width, height) = (grid.width, grid.height);
+    let mut distances = Array2::<u32>::from_elem((height as usize, width as usize), UNREACHABLE);
+    let mut frontier = VecDeque::<Int2D>::new();
+
+    for source in sources {
+        if source.x < 0 || source.y < 0 || source.x >= width || source.y >= height {
+            continue;
+        }
+        if grid.get_value(&source).is_none() {
+            distances[[source.y as usize, source.x as usize]] = 0;
+            frontier.push_back(source);
+        }
+    }
+
+    while let Some(node) = frontier.pop_front() {
+        let next_dist = distances[[node.y as usize, node.x as usize]] + 1;
+        for neib_node in neighbors(&node, width, height) {
+            let neib_dist = &mut distances[[neib_node.y as usize, neib_node.x as usize]];
+            //The field is spread from the sources, so pedestrians walk from neib_node to node
+            if *neib_dist == UNREACHABLE
+                && grid.get_value(&neib_node).is_none()
+                && !forbids(&neib_node, &node)
+            {
+                *neib_dist = next_dist;
+                frontier.push_back(neib_node);
+            }
+        }
+    }
+
+    distances
+}
+
+//Number of steps from every free cell to the nearest free cell next to an obstacle or on the
+//edge of the grid, a measure of the room around each cell
+pub fn wall_distance_field(grid: &SparseNumberGrid2D<u8>) -> Array2<u32> {
+    let (width, height) = (grid.width, grid.height);
+    let walls = (0..height)
+        .flat_map(|y| (0..width).map(move |x| Int2D { x, y }))
+        .filter(|cell| {
+            cell.x == 0
+                || cell.y == 0
+                || cell.x == width - 1
+                || cell.y == height - 1
+                || neighbors(cell, width, height)
+                    .iter()
+                    .any(|neib_node| grid.get_value(neib_node).is_some())
+        })
+        .collect::<Vec<Int2D>>();
+    distance_field(walls, grid, &|_, _| false)
+}
+
+pub fn distance_at(distances: &Array2<u32>, loc: &Int2D) -> u32 {
+    if loc.x < 0 || loc.y < 0 {
+        return UNREACHABLE;
+    }
+    distances
+        .get((loc.y as usize, loc.x as usize))
+        .copied()
+        .unwrap_or(UNREACHABLE)
+}
+
+//Walk down a distance field from origin to the nearest source. The path starts at origin and
+//stops before the source cell, which is returned separately (same convention as astar_int2d).
+//`forbids` must be the one the field was made with.
+pub fn descend(
+    distances: &Array2<u32>,
+    origin: &Int2D,
+    forbids: &dyn Fn(&Int2D, &Int2D) -> bool,
+) -> Option<(Vec<Int2D>, Int2D)> {
+    let (height, width) = distances.dim();
+    let mut current = *origin;
+    let mut current_dist = distance_at(distances, &current);
+    if current_dist == UNREACHABLE {
+        return None;
+    }
+
+    let mut path = Vec::<Int2D>::new();
+    while current_dist > 0 {
+        path.push(current);
+        let next = neighbors(&current, width as i32, height as i32)
+            .into_iter()
+            .filter(|n| !forbids(&current, n))
+            .min_by_key(|n| distance_at(distances, n))?;
+        current = next;
+        current_dist = distance_at(distances, &current);
+    }
+
+    Some((path, current))
+}
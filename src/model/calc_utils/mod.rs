@@ -1,3 +1,5 @@
+pub mod density;
+pub mod distance_field;
 pub mod navigation_distance;
 pub mod navigation_point;
 pub mod pathfinding;
@@ -115,6 +115,37 @@ pub fn astar_int2d(
     destination: &Int2D,
     grid: &SparseNumberGrid2D<u8>,
 ) -> Result<VecDeque<Int2D>, Error> {
+    astar_int2d_with_moves(origin, destination, grid, &|_, _| true)
+}
+
+//Same as astar_int2d, but a move between two free neighbouring cells is only considered
+//when move_allowed(from, to) holds (e.g. to respect one-way doors)
+pub fn astar_int2d_with_moves(
+    origin: &Int2D,
+    destination: &Int2D,
+    grid: &SparseNumberGrid2D<u8>,
+    move_allowed: &dyn Fn(&Int2D, &Int2D) -> bool,
+) -> Result<VecDeque<Int2D>, Error> {
+    astar_int2d_weighted(origin, destination, grid, &|from, to| {
+        move_allowed(from, to).then_some(1.)
+    })
+}
+
+//Costs are kept as integers in thousandths of a move
+const COST_SCALE: f32 = 1000.;
+
+//Same as astar_int2d, but moving onto a free neighbouring cell costs move_cost(from, to) times
+//a plain move, and is not considered at all when that is None. The distance estimate assumes
+//plain moves, so paths are shortest as long as no move costs less than 1.
+pub fn astar_int2d_weighted(
+    origin: &Int2D,
+    destination: &Int2D,
+    grid: &SparseNumberGrid2D<u8>,
+    move_cost: &dyn Fn(&Int2D, &Int2D) -> Option<f32>,
+) -> Result<VecDeque<Int2D>, Error> {
+    let scaled_estimate = |current: &Int2D, dest: &Int2D| {
+        get_distance_estimate(current, dest).map(|dist| dist * COST_SCALE as i32)
+    };
     let x_min = 0;
     let x_max = grid.width - 1;
     let y_min = 0;
@@ -131,12 +162,12 @@ pub fn astar_int2d(
     //Add to priority queue an item holding the node and its distance estimate
     node_queue.push(Reverse(NodeDistance {
         node: *origin,
-        dist: get_distance_estimate(origin, &destination)?,
+        dist: scaled_estimate(origin, destination)?,
     }));
 
     current_shortest_distance.insert(*origin, 0);
 
-    estimated_shortest_distance.insert(*origin, origin.manhattan_distance(&destination)?);
+    estimated_shortest_distance.insert(*origin, scaled_estimate(origin, destination)?);
 
     while let Some(Reverse(node_dist)) = node_queue.pop() {
         let NodeDistance {
@@ -145,7 +176,7 @@ pub fn astar_int2d(
         } = node_dist;
 
         //println!("Examining node {}", node_dist);
-        let current_dist = est_dist - get_distance_estimate(&node, &destination)?;
+        let current_dist = est_dist - scaled_estimate(&node, destination)?;
 
         //Remove node from tracker set...
         queued_node_set.remove(&node);
@@ -182,15 +213,20 @@ pub fn astar_int2d(
         }
 
         for neib_node in neighbors {
-            if grid.get_value(&neib_node).is_none() {
-                let added_dist = get_additional_distance(&neib_node, destination);
+            if grid.get_value(&neib_node).is_some() {
+                continue;
+            }
+            if let Some(cost) = move_cost(&node, &neib_node) {
+                let added_dist =
+                    (get_additional_distance(&neib_node, destination) as f32 * cost * COST_SCALE)
+                        .round() as i32;
                 if let Some(curr_dist) = current_shortest_distance.get(&neib_node) {
                     if *curr_dist <= current_dist + added_dist {
                         continue;
                     }
                 }
 
-                if let Ok(distance_estimate) = get_distance_estimate(&neib_node, &destination) {
+                if let Ok(distance_estimate) = scaled_estimate(&neib_node, destination) {
                     //If our new distance (dist + added_dist) is less than curr_dist OR curr_dist does not exist,
                     //update current_shortest_distance; update estimated shorted_distance; update previous position;
                     //and add neib to set for further examination
@@ -0,0 +1,52 @@
+use crate::model::fundamental_diagram::weidmann_speed;
+
+use serde::Deserialize;
+
+//Slowest fraction of the free speed assumed in a crowd, so that jammed cells cost a finite delay
+const MIN_SPEED_RATIO: f32 = 0.05;
+
+/// Rerouting around crowds. Every `interval` steps, each pedestrian looks at the cells of its
+/// remaining path within `perception_radius` metres and estimates the delay of walking them at
+/// the speed Weidmann's fundamental diagram gives for their density. When the expected delay
+/// exceeds `delay_threshold` seconds it replans, with the route cost of every cell it sees
+/// raised by `density_weight` times the density in persons/m².
+#[derive(Clone, Debug, Deserialize)]
+pub struct CongestionRerouting {
+    #[serde(default = "default_interval")]
+    pub interval: u64,
+    #[serde(default = "default_perception_radius")]
+    pub perception_radius: f32,
+    #[serde(default = "default_delay_threshold")]
+    pub delay_threshold: f32,
+    #[serde(default = "default_density_weight")]
+    pub density_weight: f32,
+}
+
+fn default_interval() -> u64 {
+    10
+}
+
+fn default_perception_radius() -> f32 {
+    10.
+}
+
+fn default_delay_threshold() -> f32 {
+    5.
+}
+
+fn default_density_weight() -> f32 {
+    1.
+}
+
+impl CongestionRerouting {
+    /// Steps lost crossing a cell at this density, for a pedestrian walking `speed` cells per step
+    pub fn cell_delay(&self, density: f32, speed: f32) -> f32 {
+        let ratio = (weidmann_speed(density) / weidmann_speed(0.)).max(MIN_SPEED_RATIO);
+        (1. / ratio - 1.) / speed.max(f32::EPSILON)
+    }
+
+    /// Factor on the route cost of a cell seen at this density
+    pub fn cost_factor(&self, density: f32) -> f32 {
+        1. + self.density_weight.max(0.) * density
+    }
+}
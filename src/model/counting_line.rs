@@ -0,0 +1,52 @@
+use krabmaga::engine::location::Real2D;
+use serde::Deserialize;
+
+/// Measurement line between two points in grid coordinates. Crossings from the right-hand
+/// side of `start -> end` to its left-hand side count as positive, the others as negative.
+/// Lines are best placed on half-cell coordinates, since pedestrians move between cell centres.
+#[derive(Clone, Debug, Deserialize)]
+pub struct CountingLine {
+    #[serde(default)]
+    pub name: String,
+    pub start: [f32; 2],
+    pub end: [f32; 2],
+}
+
+impl CountingLine {
+    pub fn length(&self) -> f32 {
+        ((self.end[0] - self.start[0]).powi(2) + (self.end[1] - self.start[1]).powi(2)).sqrt()
+    }
+
+    fn side(&self, p: &Real2D) -> f32 {
+        (self.end[0] - self.start[0]) * (p.y - self.start[1])
+            - (self.end[1] - self.start[1]) * (p.x - self.start[0])
+    }
+
+    /// Direction of a crossing by a move from `from` to `to`, if the move crosses the line:
+    /// true for positive crossings, false for negative ones
+    pub fn crossing(&self, from: &Real2D, to: &Real2D) -> Option<bool> {
+        let (side_from, side_to) = (self.side(from), self.side(to));
+        if (side_from < 0.) == (side_to < 0.) {
+            return None;
+        }
+
+        //Where along the line the move crosses it, to ignore moves past either end
+        let u = side_from / (side_from - side_to);
+        let (px, py) = (from.x + u * (to.x - from.x), from.y + u * (to.y - from.y));
+        let (lx, ly) = (self.end[0] - self.start[0], self.end[1] - self.start[1]);
+        let t = ((px - self.start[0]) * lx + (py - self.start[1]) * ly) / (lx * lx + ly * ly);
+        if !(0. ..=1.).contains(&t) {
+            return None;
+        }
+
+        Some(side_from < 0.)
+    }
+}
+
+#[derive(Copy, Clone, Debug)]
+pub struct LineCrossing {
+    pub line: usize,
+    pub step: u64,
+    pub id: u32,
+    pub positive: bool,
+}
@@ -0,0 +1,88 @@
+use crate::model::scenario::Region;
+use core::fmt;
+use serde::{Deserialize, Serialize};
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum SignalPhase {
+    Green,
+    Flashing,
+    Red,
+}
+
+impl fmt::Display for SignalPhase {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            SignalPhase::Green => write!(f, "Green"),
+            SignalPhase::Flashing => write!(f, "Flashing"),
+            SignalPhase::Red => write!(f, "Red"),
+        }
+    }
+}
+
+/// Fixed-time pedestrian signal cycle, with phase durations in steps.
+/// The cycle runs green, then flashing, then red, shifted by `offset` steps.
+#[derive(Clone, Debug, Deserialize)]
+pub struct SignalPlan {
+    pub green: u64,
+    #[serde(default)]
+    pub flashing: u64,
+    pub red: u64,
+    #[serde(default)]
+    pub offset: u64,
+}
+
+impl SignalPlan {
+    pub fn cycle_length(&self) -> u64 {
+        self.green + self.flashing + self.red
+    }
+
+    pub fn phase_at(&self, step: u64) -> SignalPhase {
+        let cycle_length = self.cycle_length();
+        if cycle_length == 0 {
+            return SignalPhase::Green;
+        }
+        let t = (step + self.offset) % cycle_length;
+        if t < self.green {
+            SignalPhase::Green
+        } else if t < self.green + self.flashing {
+            SignalPhase::Flashing
+        } else {
+            SignalPhase::Red
+        }
+    }
+}
+
+/// Signal-controlled crosswalk. Pedestrians may only step into the region from outside
+/// on green; on flashing or red they wait at the kerb, unless they decide to violate the signal.
+/// A crosswalk has no capacity, so the kerb is not a queue: everyone waiting steps on together
+/// once the signal turns green.
+#[derive(Clone, Debug, Deserialize)]
+pub struct Crossing {
+    #[serde(default)]
+    pub name: String,
+    pub region: Region,
+    pub signal: SignalPlan,
+    #[serde(default)]
+    pub violation_probability: f64,
+}
+
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct CrossingStats {
+    pub crossings: u32,
+    pub violations: u32,
+    //Steps spent at the kerb, one entry per pedestrian who had to wait
+    pub wait_steps: Vec<u64>,
+}
+
+impl CrossingStats {
+    pub fn mean_wait(&self) -> f64 {
+        if self.wait_steps.is_empty() {
+            return 0.;
+        }
+        self.wait_steps.iter().sum::<u64>() as f64 / self.wait_steps.len() as f64
+    }
+
+    pub fn max_wait(&self) -> u64 {
+        self.wait_steps.iter().copied().max().unwrap_or(0)
+    }
+}
@@ -0,0 +1,92 @@
+use crate::model::scenario::Region;
+use krabmaga::engine::location::Int2D;
+use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
+
+/// Axis direction on the obstacle grid, with y growing along raster rows
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Deserialize)]
+pub enum Direction {
+    #[serde(rename = "+x")]
+    PosX,
+    #[serde(rename = "-x")]
+    NegX,
+    #[serde(rename = "+y")]
+    PosY,
+    #[serde(rename = "-y")]
+    NegY,
+}
+
+impl Direction {
+    /// Whether the move from one cell to a neighbour goes against this direction
+    pub fn opposes(&self, from: &Int2D, to: &Int2D) -> bool {
+        let (dx, dy) = (to.x - from.x, to.y - from.y);
+        match *self {
+            Direction::PosX => dx < 0,
+            Direction::NegX => dx > 0,
+            Direction::PosY => dy < 0,
+            Direction::NegY => dy > 0,
+        }
+    }
+
+    /// Cell offset of one step this way
+    pub fn offset(&self) -> (i32, i32) {
+        match *self {
+            Direction::PosX => (1, 0),
+            Direction::NegX => (-1, 0),
+            Direction::PosY => (0, 1),
+            Direction::NegY => (0, -1),
+        }
+    }
+}
+
+/// Door, gate or turnstile. At most `flow_rate` persons per second per metre of `width` may
+/// step into the region, and if `direction` is set it can only be passed that way.
+#[derive(Clone, Debug, Deserialize)]
+pub struct Door {
+    #[serde(default)]
+    pub name: String,
+    pub region: Region,
+    pub flow_rate: f32,
+    //Clear width in metres; defaults to the narrow side of the region
+    #[serde(default)]
+    pub width: Option<f32>,
+    #[serde(default)]
+    pub direction: Option<Direction>,
+}
+
+impl Door {
+    pub fn width(&self, cell_size: f32) -> f32 {
+        self.width.unwrap_or_else(|| {
+            let cols = self.region.x_max - self.region.x_min + 1;
+            let rows = self.region.y_max - self.region.y_min + 1;
+            cols.min(rows) as f32 * cell_size
+        })
+    }
+
+    /// Persons allowed through per step
+    pub fn capacity_per_step(&self, cell_size: f32, step_duration: f32) -> f32 {
+        self.flow_rate * self.width(cell_size) * step_duration
+    }
+
+    /// Whether a move between neighbouring cells enters or crosses the door against its direction
+    pub fn forbids_move(&self, from: &Int2D, to: &Int2D) -> bool {
+        match self.direction {
+            Some(direction) => {
+                (self.region.contains(from) || self.region.contains(to))
+                    && direction.opposes(from, to)
+            }
+            None => false,
+        }
+    }
+}
+
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct DoorState {
+    //Passages available this step; refilled by the door capacity every step
+    pub tokens: f32,
+    pub passages: u32,
+    pub max_queue: usize,
+    //Pedestrians held in front of the door in the order they arrived, with the last step
+    //they tried to pass
+    pub queue: VecDeque<(u32, u64)>,
+}
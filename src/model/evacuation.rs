@@ -0,0 +1,32 @@
+use crate::model::scenario::Region;
+use serde::Deserialize;
+
+#[derive(Clone, Debug, Deserialize)]
+pub struct Exit {
+    #[serde(default)]
+    pub name: String,
+    pub region: Region,
+}
+
+/// Evacuation mode: every pedestrian heads for the exit nearest to it over the obstacle grid.
+/// With `update_interval` set, pedestrians reconsider their exit every that many steps,
+/// counting each pedestrian within `congestion_radius` cells of an exit as
+/// `congestion_weight` extra cells of distance.
+#[derive(Clone, Debug, Deserialize)]
+pub struct Evacuation {
+    pub exits: Vec<Exit>,
+    #[serde(default)]
+    pub update_interval: Option<u64>,
+    #[serde(default = "default_congestion_radius")]
+    pub congestion_radius: u32,
+    #[serde(default = "default_congestion_weight")]
+    pub congestion_weight: f32,
+}
+
+fn default_congestion_radius() -> u32 {
+    10
+}
+
+fn default_congestion_weight() -> f32 {
+    1.0
+}
@@ -0,0 +1,138 @@
+use anyhow::{anyhow, Error};
+use serde::Deserialize;
+use serde_json::{Map, Value};
+
+/// Evenly spaced numbers from `from` to `to`, inclusive
+#[derive(Clone, Debug, Deserialize)]
+pub struct ParameterRange {
+    pub from: f64,
+    pub to: f64,
+    pub step: f64,
+}
+
+/// One swept parameter. `name` is `num_agents`, `steps`, `input` (the obstacle raster) or a
+/// JSON pointer into the scenario, such as `/doors/0/flow_rate`. Values are listed in `values`,
+/// or spanned by `range`.
+#[derive(Clone, Debug, Deserialize)]
+pub struct Parameter {
+    pub name: String,
+    #[serde(default)]
+    pub values: Vec<Value>,
+    #[serde(default)]
+    pub range: Option<ParameterRange>,
+}
+
+impl Parameter {
+    pub fn levels(&self) -> Result<Vec<Value>, Error> {
+        let mut levels = self.values.clone();
+        if let Some(range) = &self.range {
+            if range.step <= 0. {
+                return Err(anyhow!("Range of {} needs a positive step", self.name));
+            }
+            let count = ((range.to - range.from) / range.step + 1e-9).floor() as usize + 1;
+            levels.extend((0..count).map(|i| {
+                //Whole numbers stay integers, so they can set counts and step numbers too
+                let level = range.from + i as f64 * range.step;
+                if level.fract() == 0. {
+                    Value::from(level as i64)
+                } else {
+                    Value::from(level)
+                }
+            }));
+        }
+        if levels.is_empty() {
+            return Err(anyhow!("Parameter {} has no values", self.name));
+        }
+        Ok(levels)
+    }
+}
+
+/// A parameter sweep: every combination of the parameters' values is run `repetitions` times,
+/// repetition r with seed `seed + r`, on top of the base settings
+#[derive(Clone, Debug, Deserialize)]
+pub struct Experiment {
+    #[serde(default)]
+    pub input: String,
+    //Base scenario file that the parameters are applied to
+    #[serde(default)]
+    pub scenario: Option<String>,
+    #[serde(default = "default_num_agents")]
+    pub num_agents: u32,
+    #[serde(default = "default_steps")]
+    pub steps: u64,
+    #[serde(default = "default_repetitions")]
+    pub repetitions: u32,
+    #[serde(default)]
+    pub seed: u64,
+    //Worker threads; all available cores if absent
+    #[serde(default)]
+    pub threads: Option<usize>,
+    #[serde(default = "default_output_dir")]
+    pub output_dir: String,
+    pub parameters: Vec<Parameter>,
+}
+
+fn default_num_agents() -> u32 {
+    2
+}
+
+fn default_steps() -> u64 {
+    100
+}
+
+fn default_repetitions() -> u32 {
+    1
+}
+
+fn default_output_dir() -> String {
+    String::from("experiment")
+}
+
+impl Experiment {
+    /// Every combination of parameter values, with the values in the order of `parameters`
+    pub fn combinations(&self) -> Result<Vec<Vec<Value>>, Error> {
+        let mut combinations: Vec<Vec<Value>> = vec![Vec::new()];
+        for parameter in self.parameters.iter() {
+            let levels = parameter.levels()?;
+            combinations = combinations
+                .into_iter()
+                .flat_map(|combination| {
+                    levels.iter().map(move |level| {
+                        let mut extended = combination.clone();
+                        extended.push(level.clone());
+                        extended
+                    })
+                })
+                .collect();
+        }
+        Ok(combinations)
+    }
+}
+
+/// Set the value at a JSON pointer, creating any missing objects on the way
+pub fn set_pointer(root: &mut Value, pointer: &str, value: Value) -> Result<(), Error> {
+    let Some(path) = pointer.strip_prefix('/') else {
+        return Err(anyhow!("{} is not a JSON pointer", pointer));
+    };
+    let mut target = root;
+    for token in path.split('/') {
+        let token = token.replace("~1", "/").replace("~0", "~");
+        if target.is_null() {
+            *target = Value::Object(Map::new());
+        }
+        target = match target {
+            Value::Array(items) => {
+                let idx: usize = token
+                    .parse()
+                    .map_err(|_| anyhow!("{} indexes an array with {}", pointer, token))?;
+                items
+                    .get_mut(idx)
+                    .ok_or_else(|| anyhow!("{} is out of bounds", pointer))?
+            }
+            Value::Object(fields) => fields.entry(token).or_insert(Value::Null),
+            _ => return Err(anyhow!("{} runs through a value", pointer)),
+        };
+    }
+    *target = value;
+    Ok(())
+}
@@ -0,0 +1,57 @@
+use serde::Deserialize;
+
+/// Measurement methods for density and speed in a measurement area, after Zhang et al. (2011):
+/// B averages over the time each pedestrian spends in the area, C is the classical
+/// count-based method per step, and D uses the pedestrians' Voronoi cells per step
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash, Deserialize)]
+pub enum FdMethod {
+    B,
+    C,
+    D,
+}
+
+/// One point of the fundamental diagram, with density in persons/m² and speed in m/s.
+/// Method B gives one point per pedestrian passing the area, stamped with the step it left
+/// the area; methods C and D give one point per step with pedestrians in the area.
+#[derive(Copy, Clone, Debug)]
+pub struct FdSample {
+    pub area: usize,
+    pub method: FdMethod,
+    pub step: u64,
+    pub id: Option<u32>,
+    pub density: f32,
+    pub speed: f32,
+}
+
+impl FdSample {
+    /// Specific flow in persons/s/m
+    pub fn flow(&self) -> f32 {
+        self.density * self.speed
+    }
+}
+
+/// Time a pedestrian has spent in a measurement area so far, for method B
+#[derive(Copy, Clone, Debug, Default)]
+pub struct AreaVisit {
+    pub steps: u32,
+    pub distance: f32,
+    //Sum over the visit of the area's count-based density
+    pub density_sum: f32,
+}
+
+//Weidmann (1993) speed-density relation for free speed 1.34 m/s and jam density 5.4 persons/m²
+const WEIDMANN_FREE_SPEED: f32 = 1.34;
+const WEIDMANN_JAM_DENSITY: f32 = 5.4;
+const WEIDMANN_GAMMA: f32 = 1.913;
+
+/// Walking speed in m/s at a density in persons/m², after Weidmann
+pub fn weidmann_speed(density: f32) -> f32 {
+    if density <= 0. {
+        return WEIDMANN_FREE_SPEED;
+    }
+    if density >= WEIDMANN_JAM_DENSITY {
+        return 0.;
+    }
+    WEIDMANN_FREE_SPEED
+        * (1. - (-WEIDMANN_GAMMA * (1. / density - 1. / WEIDMANN_JAM_DENSITY)).exp())
+}
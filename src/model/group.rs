@@ -0,0 +1,94 @@
+use krabmaga::engine::location::Real2D;
+use serde::{Deserialize, Serialize};
+
+/// Arrangement of the members around the group leader
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Formation {
+    //Abreast of the leader, alternating right and left
+    SideBySide,
+    //Leader in front, the others fanning out behind it
+    VShape,
+}
+
+impl Formation {
+    /// Offset of the k-th member (from 1) in cells, ahead of and to the right of the leader
+    pub fn offset(&self, k: usize) -> [f32; 2] {
+        let rank = k.div_ceil(2) as f32;
+        let side = if k % 2 == 1 { 1. } else { -1. };
+        match *self {
+            Formation::SideBySide => [0., side * rank],
+            Formation::VShape => [-rank, side * rank],
+        }
+    }
+}
+
+/// Social groups in the population: pedestrians in a group start around their leader, share
+/// its destination and walk in formation at the speed of the slowest member. The leader waits
+/// while any member is more than `max_gap` cells away.
+#[derive(Clone, Debug, Deserialize)]
+pub struct GroupDemand {
+    //Share of groups of each size, starting with size 1; the shares need not sum to one
+    pub size_shares: Vec<f32>,
+    #[serde(default = "default_formation")]
+    pub formation: Formation,
+    #[serde(default = "default_max_gap")]
+    pub max_gap: i32,
+}
+
+fn default_formation() -> Formation {
+    Formation::SideBySide
+}
+
+fn default_max_gap() -> i32 {
+    2
+}
+
+impl GroupDemand {
+    /// Group size for a uniform draw from [0, 1)
+    pub fn size(&self, draw: f32) -> usize {
+        let total: f32 = self.size_shares.iter().filter(|s| **s > 0.).sum();
+        if total <= 0. {
+            return 1;
+        }
+        let mut cumulative = 0.;
+        for (idx, share) in self.size_shares.iter().enumerate() {
+            cumulative += share.max(0.) / total;
+            if draw < cumulative {
+                return idx + 1;
+            }
+        }
+        self.size_shares.len()
+    }
+}
+
+/// A group on the field. `members` are the followers, with their formation offsets in `offsets`.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Group {
+    pub leader: u32,
+    pub members: Vec<u32>,
+    pub offsets: Vec<[f32; 2]>,
+}
+
+impl Group {
+    /// Place on the field for a member, given the leader's position and walking direction
+    pub fn slot(&self, member: u32, leader_loc: Real2D, heading: (f32, f32)) -> Option<Real2D> {
+        let idx = self.members.iter().position(|id| *id == member)?;
+        let [ahead, right] = self.offsets[idx];
+        //Right of the heading, with y growing down the raster
+        let (right_x, right_y) = (-heading.1, heading.0);
+        Some(Real2D {
+            x: leader_loc.x + ahead * heading.0 + right * right_x,
+            y: leader_loc.y + ahead * heading.1 + right * right_y,
+        })
+    }
+}
+
+/// Unit direction of a move, or None if there was no move
+pub fn heading(d: Real2D) -> Option<(f32, f32)> {
+    let length = (d.x * d.x + d.y * d.y).sqrt();
+    match length > 0. {
+        true => Some((d.x / length, d.y / length)),
+        false => None,
+    }
+}
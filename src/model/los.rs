@@ -0,0 +1,70 @@
+use core::fmt;
+use image::Rgb;
+
+/// Fruin level of service for walkways, from free flow (A) to breakdown (F)
+#[derive(Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum LosClass {
+    A,
+    B,
+    C,
+    D,
+    E,
+    F,
+}
+
+impl LosClass {
+    pub const ALL: [LosClass; 6] = [
+        LosClass::A,
+        LosClass::B,
+        LosClass::C,
+        LosClass::D,
+        LosClass::E,
+        LosClass::F,
+    ];
+
+    /// Class for a density in persons/m², using Fruin's walkway area-per-person thresholds
+    /// (3.3, 2.3, 1.4, 0.9 and 0.5 m² per person)
+    pub fn from_density(density: f32) -> LosClass {
+        if density < 1. / 3.3 {
+            LosClass::A
+        } else if density < 1. / 2.3 {
+            LosClass::B
+        } else if density < 1. / 1.4 {
+            LosClass::C
+        } else if density < 1. / 0.9 {
+            LosClass::D
+        } else if density < 1. / 0.5 {
+            LosClass::E
+        } else {
+            LosClass::F
+        }
+    }
+
+    pub fn index(&self) -> usize {
+        *self as usize
+    }
+
+    pub fn colour(&self) -> Rgb<u8> {
+        match *self {
+            LosClass::A => Rgb([26, 152, 80]),
+            LosClass::B => Rgb([145, 207, 96]),
+            LosClass::C => Rgb([217, 239, 139]),
+            LosClass::D => Rgb([254, 224, 139]),
+            LosClass::E => Rgb([252, 141, 89]),
+            LosClass::F => Rgb([215, 48, 39]),
+        }
+    }
+}
+
+impl fmt::Display for LosClass {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            LosClass::A => write!(f, "A"),
+            LosClass::B => write!(f, "B"),
+            LosClass::C => write!(f, "C"),
+            LosClass::D => write!(f, "D"),
+            LosClass::E => write!(f, "E"),
+            LosClass::F => write!(f, "F"),
+        }
+    }
+}
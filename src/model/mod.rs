@@ -1,4 +1,21 @@
+pub mod activity;
 pub mod calc_utils;
+pub mod congestion;
+pub mod counting_line;
+pub mod crossing;
+pub mod door;
+pub mod evacuation;
+pub mod experiment;
+pub mod fundamental_diagram;
+pub mod group;
+pub mod los;
 pub mod object;
 pub mod pedestrian;
+pub mod poi;
+pub mod profile;
+pub mod route_choice;
+pub mod scenario;
+pub mod service;
+pub mod snapshot;
 pub mod state;
+pub mod trip;
@@ -7,6 +7,7 @@ use krabmaga::engine::fields::field_2d::Location2D;
 use krabmaga::engine::location::Real2D;
 use krabmaga::engine::state::State;
 use krabmaga::rand;
+use serde::{Deserialize, Serialize};
 
 use std::hash::{Hash, Hasher};
 
@@ -20,6 +21,37 @@ pub struct Crab {
     pub dir_y: f32,
 }
 
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum PedStatus {
+    Walking,
+    Waiting,
+    Arrived,
+    Dwelling,
+}
+
+impl PedStatus {
+    /// Compact status code for numeric outputs: 0 walking, 1 waiting, 2 arrived, 3 dwelling
+    pub fn code(&self) -> u8 {
+        match *self {
+            PedStatus::Walking => 0,
+            PedStatus::Waiting => 1,
+            PedStatus::Arrived => 2,
+            PedStatus::Dwelling => 3,
+        }
+    }
+}
+
+impl fmt::Display for PedStatus {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            PedStatus::Walking => write!(f, "walking"),
+            PedStatus::Waiting => write!(f, "waiting"),
+            PedStatus::Arrived => write!(f, "arrived"),
+            PedStatus::Dwelling => write!(f, "dwelling"),
+        }
+    }
+}
+
 #[derive(Copy, Clone)]
 pub struct Pedestrian {
     pub id: u32,
@@ -28,9 +60,19 @@ pub struct Pedestrian {
     pub dest: Option<Real2D>,
     pub dir_x: f32,
     pub dir_y: f32,
+    //Cells per step at free walking speed
     pub speed: f32,
+    pub status: PedStatus,
+    //Index into the scenario's profiles
+    pub profile: usize,
+    //Body radius in metres
+    pub radius: f32,
+    //Fraction of a move carried over to the next step
+    pub progress: f32,
 }
 
+pub const DEFAULT_RADIUS: f32 = 0.25;
+
 impl Pedestrian {
     pub fn new(
         id: u32,
@@ -60,6 +102,45 @@ impl Pedestrian {
             dir_x,
             dir_y,
             speed,
+            status: PedStatus::Walking,
+            profile: 0,
+            radius: DEFAULT_RADIUS,
+            progress: 0.,
+        }
+    }
+
+    //One move along the path or in formation, or None if held back,
+    //e.g. waiting at a kerb for a green signal or for the group
+    fn advance(&mut self, state: &mut ModelState) -> Option<Real2D> {
+        //Group members keep to their formation while their leader is on the field
+        if let Some((formation_loc, standing)) = state.formation_move(self) {
+            return (!standing).then_some(formation_loc);
+        }
+        //Queueing pedestrians move up to their place in the queue
+        if let Some((queue_loc, standing)) = state.queue_move(self) {
+            return (!standing).then_some(queue_loc);
+        }
+
+        let next_point = state
+            .ped_paths
+            .get(&self.id)
+            .and_then(|path| path.as_slice().first().copied());
+        if let Some(next) = next_point {
+            if state.waits_for_group(self.id, self.loc)
+                || !state.may_advance(self.id, self.loc, next)
+            {
+                return None;
+            }
+        }
+
+        match state.ped_paths.get_mut(&self.id)?.next() {
+            Some(next_point) => Some(next_point),
+            //Waiting in front of a closure rather than finishing the trip
+            None if state.stranded_peds.contains(&self.id) => None,
+            None => Some(
+                self.dest
+                    .expect("If you have a path, you will also have a destination"),
+            ),
         }
     }
 }
@@ -68,43 +149,74 @@ impl Agent for Pedestrian {
     /// Put the code that should happen for each step, for each agent here.
     fn step(&mut self, state: &mut dyn State) {
         let state: &mut ModelState = state.as_any_mut().downcast_mut::<ModelState>().unwrap();
-        let new_loc: Real2D;
-
-        match state.ped_paths.get_mut(&self.id) {
-            Some(path_iterator) => {
-                let possible_next_point = path_iterator.next();
-                new_loc = match possible_next_point {
-                    Some(next_point) => next_point,
-                    None => self
-                        .dest
-                        .expect("If you have a path, you will also have a destination"),
-                }
-            }
-            None => {
-                let _rng = rand::thread_rng();
+        if let Some(dest) = state.pending_dests.remove(&self.id) {
+            self.dest = Some(dest);
+        }
 
-                if let Some(dest) = self.dest {
-                    (self.dir_x, self.dir_y) = normalize_motion_vector(self.loc, dest)
+        self.status = PedStatus::Walking;
+        let origin = self.loc;
+
+        if state.dwells(self.id) {
+            //Staying at a stop of the itinerary or a service point until the time is up
+            self.status = PedStatus::Dwelling;
+        } else if state.ped_paths.contains_key(&self.id) || state.group_of.contains_key(&self.id) {
+            //Whole moves this step at the pedestrian's speed, carrying the fraction over
+            self.progress += self.speed;
+            let moves = self.progress.floor();
+            self.progress -= moves;
+            for _ in 0..moves as u32 {
+                //Service starts on the move onto the service point
+                if state.dwells(self.id) {
+                    self.status = PedStatus::Dwelling;
+                    break;
+                }
+                match self.advance(state) {
+                    Some(next) => self.loc = next,
+                    None => {
+                        self.status = PedStatus::Waiting;
+                        break;
+                    }
                 }
-                let loc_x = self.loc.x + self.dir_x * self.speed;
-                let loc_y = self.loc.y + self.dir_y * self.speed;
+            }
+        } else {
+            let _rng = rand::thread_rng();
 
-                new_loc = Real2D { x: loc_x, y: loc_y };
+            if let Some(dest) = self.dest {
+                (self.dir_x, self.dir_y) = normalize_motion_vector(self.loc, dest)
             }
+            let loc_x = self.loc.x + self.dir_x * self.speed;
+            let loc_y = self.loc.y + self.dir_y * self.speed;
+
+            self.loc = Real2D { x: loc_x, y: loc_y };
         }
+        let new_loc = self.loc;
 
-        self.loc = new_loc;
+        self.last_d = Real2D {
+            x: new_loc.x - origin.x,
+            y: new_loc.y - origin.y,
+        };
 
         state.field.set_object_location(*self, new_loc);
+        state.active_peds.insert(self.id, *self);
     }
 
     /// Put the code that decides if an agent should be removed or not
     /// for example in simulation where agents can die
-    fn is_stopped(&mut self, _state: &mut dyn State) -> bool {
-        match self.dest {
+    fn is_stopped(&mut self, state: &mut dyn State) -> bool {
+        let arrived = match self.dest {
             Some(dest) => ((self.loc.x - dest.x).abs() < 1.0) & ((self.loc.y - dest.y).abs() < 1.0),
             None => false,
+        };
+        if !arrived {
+            return false;
+        }
+        let state: &mut ModelState = state.as_any_mut().downcast_mut::<ModelState>().unwrap();
+        //Stops of an itinerary are left again after dwelling
+        if state.reach_stop(self) {
+            return false;
         }
+        state.record_arrival(self);
+        true
     }
 }
 
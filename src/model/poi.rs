@@ -0,0 +1,142 @@
+use crate::model::scenario::Region;
+
+use krabmaga::engine::{fields::sparse_number_grid_2d::SparseNumberGrid2D, location::Real2D};
+use krabmaga::rand::Rng;
+use serde::Deserialize;
+
+/// Point of interest such as a shop, café, transit stop or park, in a named layer.
+/// Pedestrians choosing it as their destination head for a free cell of its region.
+#[derive(Clone, Debug, Deserialize)]
+pub struct Poi {
+    #[serde(default)]
+    pub name: String,
+    pub layer: String,
+    pub region: Region,
+    #[serde(default = "default_attractiveness")]
+    pub attractiveness: f32,
+}
+
+fn default_attractiveness() -> f32 {
+    1.
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ChoiceModel {
+    //Attraction falling with a power of the distance
+    Gravity,
+    //Multinomial logit, with utility falling linearly with the distance
+    Logit,
+}
+
+/// Destination choice among the points of interest. A POI with attractiveness A at d metres
+/// from the pedestrian is chosen with probability proportional to A^attractiveness_weight
+/// times d^-distance_decay (gravity) or exp(-distance_decay * d) (logit).
+/// With `layers`, only POIs in those layers are destinations.
+#[derive(Clone, Debug, Deserialize)]
+pub struct DestinationChoice {
+    #[serde(default = "default_model")]
+    pub model: ChoiceModel,
+    #[serde(default = "default_attractiveness")]
+    pub attractiveness_weight: f32,
+    //Defaults to 2 for gravity and 0.1 per metre for logit
+    #[serde(default)]
+    pub distance_decay: Option<f32>,
+    #[serde(default)]
+    pub layers: Vec<String>,
+}
+
+fn default_model() -> ChoiceModel {
+    ChoiceModel::Gravity
+}
+
+impl DestinationChoice {
+    pub fn distance_decay(&self) -> f32 {
+        self.distance_decay.unwrap_or(match self.model {
+            ChoiceModel::Gravity => 2.,
+            ChoiceModel::Logit => 0.1,
+        })
+    }
+
+    //Utility of a POI, up to a constant, so that probabilities are proportional to its exponential
+    fn utility(&self, attractiveness: f32, distance: f32) -> f32 {
+        let attraction = self.attractiveness_weight * attractiveness.ln();
+        match self.model {
+            ChoiceModel::Gravity => attraction - self.distance_decay() * distance.ln(),
+            ChoiceModel::Logit => attraction - self.distance_decay() * distance,
+        }
+    }
+}
+
+/// Destination choice with the free cells of every eligible point of interest
+pub struct DestinationSampler<'a> {
+    choice: &'a DestinationChoice,
+    pois: Vec<&'a Poi>,
+    cells: Vec<Vec<Real2D>>,
+    cell_size: f32,
+}
+
+impl<'a> DestinationSampler<'a> {
+    /// None without a choice model or without a point of interest to choose
+    pub fn new(
+        pois: &'a [Poi],
+        choice: Option<&'a DestinationChoice>,
+        obj_grid: &SparseNumberGrid2D<u8>,
+        cell_size: f32,
+    ) -> Option<DestinationSampler<'a>> {
+        let choice = choice?;
+        let (pois, cells): (Vec<&Poi>, Vec<Vec<Real2D>>) = pois
+            .iter()
+            .filter(|poi| {
+                poi.attractiveness > 0.
+                    && (choice.layers.is_empty() || choice.layers.contains(&poi.layer))
+            })
+            .map(|poi| (poi, poi.region.free_cells(obj_grid)))
+            .filter(|(_, cells)| !cells.is_empty())
+            .unzip();
+        if pois.is_empty() {
+            println!("No point of interest to choose destinations from, drawing them uniformly");
+            return None;
+        }
+        Some(DestinationSampler {
+            choice,
+            pois,
+            cells,
+            cell_size,
+        })
+    }
+
+    pub fn pois(&self) -> &[&'a Poi] {
+        &self.pois
+    }
+
+    /// Index of the chosen point of interest and a free cell of it, for a pedestrian at `origin`
+    pub fn draw(&self, origin: Real2D, rng: &mut impl Rng) -> (usize, Real2D) {
+        let utilities: Vec<f32> = self
+            .pois
+            .iter()
+            .map(|poi| {
+                let centre = poi.region.centre();
+                //At least a cell away, so that a POI around the origin stays finite
+                let distance = ((centre.x - origin.x).hypot(centre.y - origin.y) * self.cell_size)
+                    .max(self.cell_size);
+                self.choice.utility(poi.attractiveness, distance)
+            })
+            .collect();
+        //Exponentials relative to the best utility, which keeps them in range
+        let best = utilities.iter().copied().fold(f32::NEG_INFINITY, f32::max);
+        let weights: Vec<f32> = utilities.iter().map(|u| (u - best).exp()).collect();
+
+        let mut draw = rng.gen::<f32>() * weights.iter().sum::<f32>();
+        let mut chosen = weights.len() - 1;
+        for (idx, weight) in weights.iter().enumerate() {
+            if draw < *weight {
+                chosen = idx;
+                break;
+            }
+            draw -= weight;
+        }
+        let cells = &self.cells[chosen];
+        (chosen, cells[rng.gen_range(0..cells.len())])
+    }
+}
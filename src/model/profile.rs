@@ -0,0 +1,162 @@
+use crate::model::scenario::{Region, Zone};
+use crate::model::{calc_utils::distance_field::distance_at, pedestrian::Pedestrian};
+
+use krabmaga::engine::location::Int2D;
+use krabmaga::rand::Rng;
+use ndarray::Array2;
+use serde::Deserialize;
+use std::f32::consts::PI;
+
+//Route cost factor of cells too narrow for a pedestrian's body, which it squeezes through
+//only when there is no other way
+const SQUEEZE_FACTOR: f32 = 10.;
+
+/// Normal distribution of a pedestrian attribute, cut off at `min` and `max`.
+/// With `sd` left out every pedestrian gets the mean.
+#[derive(Clone, Debug, Deserialize)]
+pub struct Spread {
+    pub mean: f32,
+    #[serde(default)]
+    pub sd: f32,
+    #[serde(default)]
+    pub min: Option<f32>,
+    #[serde(default)]
+    pub max: Option<f32>,
+}
+
+impl Spread {
+    pub fn sample(&self, rng: &mut impl Rng) -> f32 {
+        let mut value = self.mean;
+        if self.sd > 0. {
+            //Box-Muller transform of two uniform draws
+            let (u1, u2): (f32, f32) = (rng.gen_range(f32::EPSILON..1.), rng.gen());
+            value += self.sd * (-2. * u1.ln()).sqrt() * (2. * PI * u2).cos();
+        }
+        if let Some(min) = self.min {
+            value = value.max(min);
+        }
+        if let Some(max) = self.max {
+            value = value.min(max);
+        }
+        value
+    }
+}
+
+/// Cost factor for routes through a named zone of the scenario's `route_zones`:
+/// above 1 the zone is avoided, below 1 it is preferred, by making the cells outside it
+/// 1 / `weight` times as costly
+#[derive(Clone, Debug, Deserialize)]
+pub struct ZonePreference {
+    pub zone: String,
+    pub weight: f32,
+}
+
+/// Kind of pedestrian, such as commuter, tourist or wheelchair user, making up `share` of the
+/// population. Free speed is in m/s, body radius and preferred wall clearance in metres.
+/// `stair_avoidance` adds to the route cost of stair cells, and without `uses_stairs` stairs
+/// are not used at all.
+#[derive(Clone, Debug, Deserialize)]
+pub struct Profile {
+    pub name: String,
+    pub share: f32,
+    #[serde(default = "default_free_speed")]
+    pub free_speed: Spread,
+    #[serde(default = "default_radius")]
+    pub radius: Spread,
+    #[serde(default)]
+    pub clearance: f32,
+    #[serde(default)]
+    pub stair_avoidance: f32,
+    #[serde(default = "default_true")]
+    pub uses_stairs: bool,
+    #[serde(default)]
+    pub zone_preferences: Vec<ZonePreference>,
+}
+
+fn default_free_speed() -> Spread {
+    Spread {
+        mean: 1.34,
+        sd: 0.26,
+        min: Some(0.3),
+        max: Some(2.5),
+    }
+}
+
+fn default_radius() -> Spread {
+    Spread {
+        mean: 0.25,
+        sd: 0.,
+        min: None,
+        max: None,
+    }
+}
+
+fn default_true() -> bool {
+    true
+}
+
+/// Index of the profile for a uniform draw from [0, 1), by the profiles' shares
+pub fn choose_profile(profiles: &[Profile], draw: f32) -> usize {
+    let total: f32 = profiles.iter().map(|p| p.share.max(0.)).sum();
+    if total <= 0. {
+        return 0;
+    }
+    let mut cumulative = 0.;
+    for (idx, profile) in profiles.iter().enumerate() {
+        cumulative += profile.share.max(0.) / total;
+        if draw < cumulative {
+            return idx;
+        }
+    }
+    profiles.len() - 1
+}
+
+/// Per-pedestrian route costs on the obstacle grid, following the pedestrian's profile.
+/// Entering a cell costs 1, more near walls, on stairs, in avoided zones and outside preferred ones.
+pub struct RouteCosts<'a> {
+    pub profiles: &'a [Profile],
+    //Cells from every free cell to the nearest cell next to an obstacle or the grid edge
+    pub wall_distances: &'a Array2<u32>,
+    pub stairs: &'a [Region],
+    pub zones: &'a [Zone],
+    pub cell_size: f32,
+}
+
+impl RouteCosts<'_> {
+    /// Cost of stepping onto `cell`, or None if the pedestrian will not go there
+    pub fn cell_cost(&self, ped: &Pedestrian, cell: &Int2D) -> Option<f32> {
+        let Some(profile) = self.profiles.get(ped.profile) else {
+            return Some(1.);
+        };
+        let mut cost = 1.;
+
+        //Metres from the middle of the cell to the nearest wall
+        let clearance = (distance_at(self.wall_distances, cell) as f32 + 0.5) * self.cell_size;
+        cost += (profile.clearance - clearance).max(0.) / self.cell_size;
+        if clearance < ped.radius {
+            cost *= SQUEEZE_FACTOR;
+        }
+
+        if self.stairs.iter().any(|stairs| stairs.contains(cell)) {
+            if !profile.uses_stairs {
+                return None;
+            }
+            cost *= 1. + profile.stair_avoidance;
+        }
+
+        //Costs stay at 1 or more for the A* heuristic, so a preferred zone makes every cell
+        //outside it dearer rather than its own cells cheaper
+        for preference in profile.zone_preferences.iter() {
+            let inside = self
+                .zones
+                .iter()
+                .any(|zone| zone.name == preference.zone && zone.region.contains(cell));
+            match (inside, preference.weight >= 1.) {
+                (true, true) => cost *= preference.weight,
+                (false, false) => cost /= preference.weight.max(0.1),
+                _ => {}
+            }
+        }
+        Some(cost)
+    }
+}
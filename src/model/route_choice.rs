@@ -0,0 +1,103 @@
+use krabmaga::engine::location::Int2D;
+use serde::Deserialize;
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RouteChoiceModel {
+    //Cheapest route under route costs randomly perturbed for each pedestrian
+    Perturbation,
+    //Logit choice among up to k diverse candidate routes
+    Logit,
+}
+
+/// How pedestrians spread over alternative routes instead of all taking the cheapest one.
+/// With `perturbation`, each pedestrian sees every square patch of `patch_size` cells as up to
+/// `spread` (a fraction) dearer. With `logit`, up to `k` candidate routes are found
+/// by making the cells of the routes found so far `overlap_penalty` times as costly, and one
+/// is chosen with probability proportional to exp(-theta * cost), costs counted in cells of
+/// unhindered walking.
+#[derive(Clone, Debug, Deserialize)]
+pub struct RouteChoice {
+    pub model: RouteChoiceModel,
+    #[serde(default = "default_spread")]
+    pub spread: f32,
+    #[serde(default = "default_patch_size")]
+    pub patch_size: i32,
+    #[serde(default = "default_k")]
+    pub k: usize,
+    #[serde(default = "default_theta")]
+    pub theta: f32,
+    #[serde(default = "default_overlap_penalty")]
+    pub overlap_penalty: f32,
+}
+
+fn default_spread() -> f32 {
+    0.3
+}
+
+fn default_patch_size() -> i32 {
+    3
+}
+
+fn default_k() -> usize {
+    3
+}
+
+fn default_theta() -> f32 {
+    0.5
+}
+
+fn default_overlap_penalty() -> f32 {
+    1.5
+}
+
+//SplitMix64 finaliser, spreading close inputs over the whole range
+fn mix(mut x: u64) -> u64 {
+    x = x.wrapping_add(0x9E37_79B9_7F4A_7C15);
+    x = (x ^ (x >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+    x = (x ^ (x >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+    x ^ (x >> 31)
+}
+
+/// Uniform draw from [0, 1) fixed by the seed and the keys, so that the same pedestrian
+/// makes the same choices whenever it plans a route, and replays repeat them
+pub fn keyed_uniform(seed: u64, keys: &[i64]) -> f32 {
+    let hash = keys
+        .iter()
+        .fold(mix(seed), |hash, key| mix(hash ^ *key as u64));
+    (hash >> 40) as f32 / (1u64 << 24) as f32
+}
+
+impl RouteChoice {
+    /// Factor on the route cost of a cell as seen by pedestrian `id`
+    pub fn perturbation(&self, seed: u64, id: u32, cell: &Int2D) -> f32 {
+        let patch = self.patch_size.max(1);
+        let draw = keyed_uniform(
+            seed,
+            &[
+                id as i64,
+                cell.x.div_euclid(patch) as i64,
+                cell.y.div_euclid(patch) as i64,
+            ],
+        );
+        //Only ever dearer, as the A* heuristic needs costs of 1 or more
+        1. + self.spread.max(0.) * draw
+    }
+
+    /// Index of the candidate route chosen for a uniform draw from [0, 1), by route cost
+    pub fn choose(&self, costs: &[f32], draw: f32) -> usize {
+        let best = costs.iter().copied().fold(f32::INFINITY, f32::min);
+        let weights: Vec<f32> = costs
+            .iter()
+            .map(|cost| (-self.theta * (cost - best)).exp())
+            .collect();
+        let mut draw = draw * weights.iter().sum::<f32>();
+        for (idx, weight) in weights.iter().enumerate() {
+            if draw < *weight {
+                return idx;
+            }
+            draw -= weight;
+        }
+        costs.len().saturating_sub(1)
+    }
+}
@@ -0,0 +1,368 @@
+use crate::model::{
+    activity::ActivityChain,
+    calc_utils::density::DensityMethod,
+    congestion::CongestionRerouting,
+    counting_line::CountingLine,
+    crossing::Crossing,
+    door::Door,
+    evacuation::Evacuation,
+    fundamental_diagram::FdMethod,
+    group::GroupDemand,
+    poi::{DestinationChoice, Poi},
+    profile::Profile,
+    route_choice::RouteChoice,
+    service::ServicePoint,
+};
+use itertools::iproduct;
+use krabmaga::engine::{
+    fields::sparse_number_grid_2d::SparseNumberGrid2D,
+    location::{Int2D, Real2D},
+};
+use serde::Deserialize;
+
+/// Inclusive rectangle of grid cells, in the same (col, row) coordinates as `obj_grid`.
+#[derive(Clone, Copy, Debug, Deserialize)]
+pub struct Region {
+    pub x_min: i32,
+    pub y_min: i32,
+    pub x_max: i32,
+    pub y_max: i32,
+}
+
+impl Region {
+    pub fn contains(&self, loc: &Int2D) -> bool {
+        loc.x >= self.x_min && loc.x <= self.x_max && loc.y >= self.y_min && loc.y <= self.y_max
+    }
+
+    pub fn cells(&self) -> impl Iterator<Item = Int2D> {
+        iproduct!(self.x_min..=self.x_max, self.y_min..=self.y_max).map(|(x, y)| Int2D { x, y })
+    }
+
+    /// Cells of the region on the grid and free of obstacles, as positions on the field
+    pub fn free_cells(&self, obj_grid: &SparseNumberGrid2D<u8>) -> Vec<Real2D> {
+        self.cells()
+            .filter(|cell| {
+                cell.x >= 0
+                    && cell.y >= 0
+                    && cell.x < obj_grid.width
+                    && cell.y < obj_grid.height
+                    && obj_grid.get_value(cell).is_none()
+            })
+            .map(|cell| Real2D {
+                x: cell.x as f32,
+                y: cell.y as f32,
+            })
+            .collect()
+    }
+
+    pub fn centre(&self) -> Real2D {
+        Real2D {
+            x: (self.x_min + self.x_max) as f32 / 2.,
+            y: (self.y_min + self.y_max) as f32 / 2.,
+        }
+    }
+}
+
+/// Named region used for aggregating outputs
+#[derive(Clone, Debug, Deserialize)]
+pub struct Zone {
+    pub name: String,
+    pub region: Region,
+}
+
+/// A region of the obstacle grid that is closed between `start_step` and `end_step`.
+/// With a `period`, the closure repeats and the steps are taken modulo the period.
+#[derive(Clone, Debug, Deserialize)]
+pub struct ObstacleEvent {
+    #[serde(default)]
+    pub name: String,
+    pub region: Region,
+    pub start_step: u64,
+    #[serde(default)]
+    pub end_step: Option<u64>,
+    #[serde(default)]
+    pub period: Option<u64>,
+}
+
+impl ObstacleEvent {
+    pub fn is_active(&self, step: u64) -> bool {
+        let step = match self.period {
+            Some(period) if period > 0 => step % period,
+            _ => step,
+        };
+        match self.end_step {
+            Some(end_step) => step >= self.start_step && step < end_step,
+            None => step >= self.start_step,
+        }
+    }
+}
+
+/// Per-step trajectory logging, every `interval` steps, to CSV and optionally Parquet
+#[derive(Clone, Debug, Deserialize)]
+pub struct TrajectoryOutput {
+    #[serde(default = "default_interval")]
+    pub interval: u64,
+    #[serde(default)]
+    pub parquet: bool,
+}
+
+fn default_interval() -> u64 {
+    1
+}
+
+/// Density maps in persons/m² on the obstacle grid, sampled every `interval` steps and
+/// averaged over the whole run and, with `window` set, over consecutive windows of that many steps.
+/// `bandwidth` is the kernel bandwidth, or the largest Voronoi cell radius, in metres.
+/// Maps are coloured from 0 to `max_density` in the PNG output.
+#[derive(Clone, Debug, Deserialize)]
+pub struct DensityOutput {
+    #[serde(default = "default_density_method")]
+    pub method: DensityMethod,
+    #[serde(default = "default_bandwidth")]
+    pub bandwidth: f32,
+    #[serde(default = "default_interval")]
+    pub interval: u64,
+    #[serde(default)]
+    pub window: Option<u64>,
+    #[serde(default = "default_max_density")]
+    pub max_density: f32,
+    #[serde(default = "default_true")]
+    pub png: bool,
+    #[serde(default = "default_true")]
+    pub geotiff: bool,
+}
+
+fn default_density_method() -> DensityMethod {
+    DensityMethod::Kernel
+}
+
+fn default_bandwidth() -> f32 {
+    1.0
+}
+
+fn default_max_density() -> f32 {
+    2.0
+}
+
+fn default_true() -> bool {
+    true
+}
+
+/// Fruin level of service per cell and per zone, sampled every `interval` steps
+#[derive(Clone, Debug, Deserialize)]
+pub struct LosOutput {
+    #[serde(default = "default_interval")]
+    pub interval: u64,
+    #[serde(default)]
+    pub zones: Vec<Zone>,
+}
+
+/// Speed-density and flow-density measurements in the measurement `areas` with each of `methods`.
+/// `voronoi_radius` bounds the Voronoi cells of method D, in metres. The PNG plot shows
+/// densities up to `max_density`, speeds up to 2 m/s and specific flows up to 2 persons/s/m.
+#[derive(Clone, Debug, Deserialize)]
+pub struct FundamentalDiagramOutput {
+    pub areas: Vec<Zone>,
+    #[serde(default = "default_fd_methods")]
+    pub methods: Vec<FdMethod>,
+    #[serde(default = "default_voronoi_radius")]
+    pub voronoi_radius: f32,
+    #[serde(default = "default_fd_max_density")]
+    pub max_density: f32,
+    #[serde(default = "default_true")]
+    pub png: bool,
+}
+
+fn default_fd_methods() -> Vec<FdMethod> {
+    vec![FdMethod::B, FdMethod::C, FdMethod::D]
+}
+
+fn default_voronoi_radius() -> f32 {
+    2.0
+}
+
+fn default_fd_max_density() -> f32 {
+    6.0
+}
+
+/// Headless rendering of the obstacle grid and pedestrians, every `interval` steps and at each
+/// of `steps` (every step if neither is given), with `scale` pixels per cell. With `heatmap`,
+/// the density map coloured from 0 to `max_density` is drawn underneath the pedestrians.
+#[derive(Clone, Debug, Deserialize)]
+pub struct FrameOutput {
+    #[serde(default)]
+    pub interval: Option<u64>,
+    #[serde(default)]
+    pub steps: Vec<u64>,
+    #[serde(default = "default_scale")]
+    pub scale: u32,
+    #[serde(default)]
+    pub heatmap: bool,
+    #[serde(default = "default_max_density")]
+    pub max_density: f32,
+    #[serde(default = "default_true")]
+    pub png: bool,
+    #[serde(default)]
+    pub gif: bool,
+    #[serde(default = "default_frame_delay")]
+    pub frame_delay_ms: u32,
+}
+
+impl FrameOutput {
+    pub fn renders(&self, step: u64) -> bool {
+        match self.interval {
+            Some(interval) => {
+                (interval > 0 && step.is_multiple_of(interval)) || self.steps.contains(&step)
+            }
+            None => self.steps.is_empty() || self.steps.contains(&step),
+        }
+    }
+}
+
+fn default_scale() -> u32 {
+    4
+}
+
+fn default_frame_delay() -> u32 {
+    100
+}
+
+/// Encoding of the streamed frames: JSON text, or little-endian binary with the run (u32), step
+/// (u64) and pedestrian count (u32), then id (u32), x, y, vx, vy (f32) and status (u8) per pedestrian
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum StreamFormat {
+    Json,
+    Binary,
+}
+
+/// Longitude and latitude of the grid's top left corner, for placing streamed runs on a map
+#[derive(Copy, Clone, Debug, Deserialize)]
+pub struct GeoOrigin {
+    pub lon: f64,
+    pub lat: f64,
+}
+
+impl GeoOrigin {
+    /// Longitude and latitude of a point `x` metres east and `y` metres south of the origin
+    pub fn lon_lat(&self, x: f32, y: f32) -> (f64, f64) {
+        let metres_per_degree_lat = 110_540.;
+        let metres_per_degree_lon = 111_320. * self.lat.to_radians().cos();
+        (
+            self.lon + x as f64 / metres_per_degree_lon,
+            self.lat - y as f64 / metres_per_degree_lat,
+        )
+    }
+}
+
+/// Live stream of pedestrian positions and status over a WebSocket on `address`, every
+/// `interval` steps. With an `origin`, JSON frames carry longitude and latitude as well.
+#[derive(Clone, Debug, Deserialize)]
+pub struct StreamOutput {
+    #[serde(default = "default_stream_address")]
+    pub address: String,
+    #[serde(default = "default_interval")]
+    pub interval: u64,
+    #[serde(default = "default_stream_format")]
+    pub format: StreamFormat,
+    #[serde(default)]
+    pub origin: Option<GeoOrigin>,
+}
+
+fn default_stream_address() -> String {
+    String::from("127.0.0.1:9001")
+}
+
+fn default_stream_format() -> StreamFormat {
+    StreamFormat::Json
+}
+
+/// Population of the runs after the first: drawn anew, or the first run's population replayed
+/// with the same random number sequence
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum PopulationMode {
+    Resample,
+    Replay,
+}
+
+/// Everything about a run that is not in the obstacle raster
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default)]
+pub struct Scenario {
+    //Side of one grid cell in metres
+    pub cell_size: f32,
+    //Simulated seconds per step
+    pub step_duration: f32,
+    //Directory that run outputs are written to
+    pub output_dir: String,
+    pub obstacle_events: Vec<ObstacleEvent>,
+    pub crossings: Vec<Crossing>,
+    pub doors: Vec<Door>,
+    pub evacuation: Option<Evacuation>,
+    pub trajectories: Option<TrajectoryOutput>,
+    pub density: Option<DensityOutput>,
+    pub los: Option<LosOutput>,
+    pub counting_lines: Vec<CountingLine>,
+    //Steps per aggregation interval of the counting line flows
+    pub count_interval: u64,
+    pub fundamental_diagram: Option<FundamentalDiagramOutput>,
+    pub frames: Option<FrameOutput>,
+    pub stream: Option<StreamOutput>,
+    //Seed of the random number generator; a random seed is drawn when absent
+    pub seed: Option<u64>,
+    //Steps after which the full simulation state is written as a snapshot
+    pub snapshot_steps: Vec<u64>,
+    pub population: PopulationMode,
+    pub groups: Option<GroupDemand>,
+    //Kinds of pedestrians and their shares of the population; everyone is alike when empty
+    pub profiles: Vec<Profile>,
+    //Stair cells, avoided by some profiles
+    pub stairs: Vec<Region>,
+    //Named regions that profiles may prefer or avoid on their routes
+    pub route_zones: Vec<Zone>,
+    //Stops that parts of the population make on their way
+    pub activity_chains: Vec<ActivityChain>,
+    //Points of interest, and how destinations are chosen among them instead of uniformly
+    pub pois: Vec<Poi>,
+    pub destination_choice: Option<DestinationChoice>,
+    pub route_choice: Option<RouteChoice>,
+    pub congestion: Option<CongestionRerouting>,
+    //Ticket machines, gates, bus doors and counters with their queues
+    pub services: Vec<ServicePoint>,
+}
+
+impl Default for Scenario {
+    fn default() -> Self {
+        Scenario {
+            cell_size: 1.0,
+            step_duration: 1.0,
+            output_dir: String::from("output"),
+            obstacle_events: Vec::new(),
+            crossings: Vec::new(),
+            doors: Vec::new(),
+            evacuation: None,
+            trajectories: None,
+            density: None,
+            los: None,
+            counting_lines: Vec::new(),
+            count_interval: 60,
+            fundamental_diagram: None,
+            frames: None,
+            stream: None,
+            seed: None,
+            snapshot_steps: Vec::new(),
+            population: PopulationMode::Resample,
+            groups: None,
+            profiles: Vec::new(),
+            stairs: Vec::new(),
+            route_zones: Vec::new(),
+            activity_chains: Vec::new(),
+            pois: Vec::new(),
+            destination_choice: None,
+            route_choice: None,
+            congestion: None,
+            services: Vec::new(),
+        }
+    }
+}
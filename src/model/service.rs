@@ -0,0 +1,120 @@
+use std::collections::{HashMap, VecDeque};
+
+use crate::model::{door::Direction, profile::Spread, scenario::Region};
+
+use krabmaga::engine::{
+    fields::sparse_number_grid_2d::SparseNumberGrid2D,
+    location::{Int2D, Real2D},
+};
+use serde::{Deserialize, Serialize};
+
+/// Ticket machine, gate, bus door or coffee counter. Pedestrians whose path enters `region` join
+/// a first-come first-served queue that starts at `queue_start` and grows along
+/// `queue_direction`, one cell per person. Up to `servers` pedestrians are served at once, each
+/// for `service_time` seconds, standing at the first cell of the region on their way.
+#[derive(Clone, Debug, Deserialize)]
+pub struct ServicePoint {
+    #[serde(default)]
+    pub name: String,
+    pub region: Region,
+    #[serde(default = "default_servers")]
+    pub servers: usize,
+    pub service_time: Spread,
+    pub queue_start: [i32; 2],
+    pub queue_direction: Direction,
+    //Places the queue has in a row from its start, set when the scenario is loaded
+    #[serde(skip)]
+    pub queue_room: usize,
+}
+
+fn default_servers() -> usize {
+    1
+}
+
+impl ServicePoint {
+    /// Cell of the k-th place in the queue, counted from 0 at the head.
+    /// Pedestrians beyond the room the queue has wait at its last place.
+    pub fn queue_slot(&self, k: usize) -> Real2D {
+        let k = k.min(self.queue_room.saturating_sub(1));
+        let (dx, dy) = self.queue_direction.offset();
+        Real2D {
+            x: (self.queue_start[0] + dx * k as i32) as f32,
+            y: (self.queue_start[1] + dy * k as i32) as f32,
+        }
+    }
+
+    /// Number of places in a row, up to `max`, that the queue has from its start before it
+    /// runs off the grid, into an obstacle or onto the service point
+    pub fn count_queue_room(&self, obj_grid: &SparseNumberGrid2D<u8>, max: usize) -> usize {
+        let (dx, dy) = self.queue_direction.offset();
+        (0..max)
+            .take_while(|k| {
+                let cell = Int2D {
+                    x: self.queue_start[0] + dx * *k as i32,
+                    y: self.queue_start[1] + dy * *k as i32,
+                };
+                cell.x >= 0
+                    && cell.y >= 0
+                    && cell.x < obj_grid.width
+                    && cell.y < obj_grid.height
+                    && obj_grid.get_value(&cell).is_none()
+                    && !self.region.contains(&cell)
+            })
+            .count()
+    }
+}
+
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct ServiceState {
+    //Waiting pedestrians, the head first, with the step each one joined
+    pub queue: VecDeque<u32>,
+    pub joined: HashMap<u32, u64>,
+    //Pedestrians called to the service point or being served
+    pub serving: Vec<u32>,
+    pub served: u32,
+    //Waiting time in seconds of every pedestrian served
+    pub waits: Vec<f32>,
+    pub max_queue: usize,
+    pub queue_length_sum: u64,
+    pub samples: u64,
+}
+
+/// Queue and waiting time indicators of one service point over a run, times in seconds
+#[derive(Clone, Debug, Default, Serialize)]
+pub struct ServiceSummary {
+    pub name: String,
+    pub served: u32,
+    pub still_queueing: usize,
+    pub mean_queue_length: f32,
+    pub max_queue_length: usize,
+    pub mean_wait: f32,
+    pub p90_wait: f32,
+    pub max_wait: f32,
+}
+
+impl ServiceSummary {
+    pub fn new(service: &ServicePoint, state: &ServiceState) -> ServiceSummary {
+        let mut waits = state.waits.clone();
+        waits.sort_by(|a, b| a.total_cmp(b));
+        let p90_wait = match waits.is_empty() {
+            true => 0.,
+            false => waits[((0.9 * waits.len() as f32).ceil() as usize).clamp(1, waits.len()) - 1],
+        };
+        ServiceSummary {
+            name: service.name.clone(),
+            served: state.served,
+            still_queueing: state.queue.len(),
+            mean_queue_length: match state.samples {
+                0 => 0.,
+                samples => state.queue_length_sum as f32 / samples as f32,
+            },
+            max_queue_length: state.max_queue,
+            mean_wait: match waits.is_empty() {
+                true => 0.,
+                false => waits.iter().sum::<f32>() / waits.len() as f32,
+            },
+            p90_wait,
+            max_wait: waits.last().copied().unwrap_or(0.),
+        }
+    }
+}
@@ -0,0 +1,161 @@
+use std::collections::HashMap;
+
+use crate::model::{
+    activity::Itinerary,
+    crossing::CrossingStats,
+    door::DoorState,
+    group::Group,
+    pedestrian::{PedStatus, Pedestrian, DEFAULT_RADIUS},
+    service::ServiceState,
+    trip::TripRecord,
+};
+
+use krabmaga::engine::location::{Int2D, Real2D};
+use rand_chacha::{rand_core::SeedableRng, ChaCha8Rng};
+use serde::{Deserialize, Serialize};
+
+/// Pedestrian with its positions as plain coordinates
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct PedestrianRecord {
+    pub id: u32,
+    pub loc: [f32; 2],
+    pub last_d: [f32; 2],
+    pub dest: Option<[f32; 2]>,
+    pub dir_x: f32,
+    pub dir_y: f32,
+    pub speed: f32,
+    pub status: PedStatus,
+    #[serde(default)]
+    pub profile: usize,
+    #[serde(default = "default_radius")]
+    pub radius: f32,
+    #[serde(default)]
+    pub progress: f32,
+}
+
+fn default_radius() -> f32 {
+    DEFAULT_RADIUS
+}
+
+pub fn to_point(loc: &Real2D) -> [f32; 2] {
+    [loc.x, loc.y]
+}
+
+pub fn from_point(point: &[f32; 2]) -> Real2D {
+    Real2D {
+        x: point[0],
+        y: point[1],
+    }
+}
+
+pub fn to_cell_point(cell: &Int2D) -> [i32; 2] {
+    [cell.x, cell.y]
+}
+
+pub fn from_cell_point(point: &[i32; 2]) -> Int2D {
+    Int2D {
+        x: point[0],
+        y: point[1],
+    }
+}
+
+impl From<&Pedestrian> for PedestrianRecord {
+    fn from(ped: &Pedestrian) -> Self {
+        PedestrianRecord {
+            id: ped.id,
+            loc: to_point(&ped.loc),
+            last_d: to_point(&ped.last_d),
+            dest: ped.dest.as_ref().map(to_point),
+            dir_x: ped.dir_x,
+            dir_y: ped.dir_y,
+            speed: ped.speed,
+            status: ped.status,
+            profile: ped.profile,
+            radius: ped.radius,
+            progress: ped.progress,
+        }
+    }
+}
+
+impl From<&PedestrianRecord> for Pedestrian {
+    fn from(record: &PedestrianRecord) -> Self {
+        Pedestrian {
+            id: record.id,
+            loc: from_point(&record.loc),
+            last_d: from_point(&record.last_d),
+            dest: record.dest.as_ref().map(from_point),
+            dir_x: record.dir_x,
+            dir_y: record.dir_y,
+            speed: record.speed,
+            status: record.status,
+            profile: record.profile,
+            radius: record.radius,
+            progress: record.progress,
+        }
+    }
+}
+
+/// Position of a ChaCha random number generator in its stream
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct RngState {
+    pub seed: [u8; 32],
+    pub stream: u64,
+    pub word_pos: u128,
+}
+
+impl RngState {
+    pub fn capture(rng: &ChaCha8Rng) -> RngState {
+        RngState {
+            seed: rng.get_seed(),
+            stream: rng.get_stream(),
+            word_pos: rng.get_word_pos(),
+        }
+    }
+
+    pub fn restore(&self) -> ChaCha8Rng {
+        let mut rng = ChaCha8Rng::from_seed(self.seed);
+        rng.set_stream(self.stream);
+        rng.set_word_pos(self.word_pos);
+        rng
+    }
+}
+
+/// Everything needed to continue a run from the end of `step`: the pedestrians still on the field
+/// as the agents last left them, what remains of their paths, the obstacle grid including applied
+/// closures, the state of signals, doors, evacuation and trips, and the random number generator.
+/// Outputs accumulated before the snapshot (density, LOS, counts and so on) are not included.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Snapshot {
+    pub step: u64,
+    pub dim: (f32, f32),
+    //Initial pedestrian records, as created for the run
+    pub peds: Vec<PedestrianRecord>,
+    //Pedestrians still on the field, as of the last step
+    pub agents: Vec<PedestrianRecord>,
+    pub paths: HashMap<u32, Vec<[f32; 2]>>,
+    //Obstacle cells of the raster, without the closures applied on top of it
+    pub obstacles: Vec<[i32; 2]>,
+    pub pending_dests: HashMap<u32, [f32; 2]>,
+    pub applied_closures: HashMap<usize, Vec<[i32; 2]>>,
+    pub stranded_peds: Vec<u32>,
+    pub crossing_stats: Vec<CrossingStats>,
+    pub crossing_waits: HashMap<u32, (usize, u64)>,
+    pub door_states: Vec<DoorState>,
+    pub exit_choice: HashMap<u32, usize>,
+    pub evacuated: Vec<(u32, usize, u64)>,
+    pub trips: Vec<TripRecord>,
+    pub rng: RngState,
+    //Groups still walking together; absent in snapshots of runs without groups
+    #[serde(default)]
+    pub groups: Vec<Group>,
+    #[serde(default)]
+    pub itineraries: HashMap<u32, Itinerary>,
+    #[serde(default)]
+    pub dwelling: HashMap<u32, u64>,
+    #[serde(default)]
+    pub route_seed: u64,
+    #[serde(default)]
+    pub service_states: Vec<ServiceState>,
+    #[serde(default)]
+    pub service_passes: HashMap<u32, u64>,
+}
@@ -0,0 +1,87 @@
+use std::collections::HashMap;
+
+use crate::model::{
+    pedestrian::Pedestrian,
+    state::{components::make_itineraries, state::ModelState},
+};
+
+impl ModelState {
+    /// Draw the itineraries of the current population, before its groups are formed.
+    /// Evacuees head for the exits and make no stops.
+    pub fn draw_itineraries(&mut self) {
+        self.itineraries = match self.evacuation {
+            Some(_) => HashMap::new(),
+            None => make_itineraries(
+                &mut self.peds,
+                &self.activity_chains,
+                &self.obj_grid,
+                self.step_duration,
+                &mut self.rng,
+            ),
+        };
+    }
+
+    /// Whether a pedestrian is still spending its dwell time at a stop
+    pub fn dwells(&mut self, id: u32) -> bool {
+        match self.dwelling.get(&id) {
+            Some(until) if self.step < *until => true,
+            Some(_) => {
+                self.dwelling.remove(&id);
+                false
+            }
+            None => false,
+        }
+    }
+
+    /// Called when a pedestrian reaches its destination. At a stop of its itinerary it starts
+    /// dwelling, with the route of the next leg planned, and true is returned; at the end of
+    /// its itinerary, or without one, the trip is over.
+    pub fn reach_stop(&mut self, ped: &Pedestrian) -> bool {
+        //Group members go with their leader until its last leg
+        if let Some(group) = self.group_of.get(&ped.id).map(|idx| &self.groups[*idx]) {
+            if group.leader != ped.id {
+                return self
+                    .itineraries
+                    .get(&group.leader)
+                    .is_some_and(|itinerary| itinerary.next_legs().is_some());
+            }
+        }
+
+        let Some((finished, next)) = self
+            .itineraries
+            .get(&ped.id)
+            .and_then(|itinerary| itinerary.next_legs())
+        else {
+            return false;
+        };
+        let dest = next.dest();
+        let path = match self.plan_route(ped, ped.loc, dest) {
+            Ok(path) => path,
+            Err(e) => {
+                println!("Pedestrian {} cannot go on from its stop: {}", ped.id, e);
+                return false;
+            }
+        };
+        if let Some(itinerary) = self.itineraries.get_mut(&ped.id) {
+            itinerary.leg += 1;
+        }
+        //Arrivals happen during the current step, so dwelling starts with the next one
+        if finished.dwell_steps > 0 {
+            self.dwelling
+                .insert(ped.id, self.step + 1 + finished.dwell_steps);
+        }
+
+        //Members take the next leg with their leader
+        let members = self
+            .group_of
+            .get(&ped.id)
+            .map(|idx| self.groups[*idx].members.clone())
+            .unwrap_or_default();
+        for id in std::iter::once(ped.id).chain(members) {
+            self.pending_dests.insert(id, dest);
+            self.extend_trip(id, path.len(), ped.speed, finished.dwell_steps);
+        }
+        self.ped_paths.insert(ped.id, path.into_iter());
+        true
+    }
+}
@@ -1,8 +1,13 @@
 use crate::model::{
+    activity::{choose_chain, ActivityChain, Itinerary, Leg},
     calc_utils::navigation_distance::*,
-    calc_utils::navigation_point::*,
+    calc_utils::pathfinding::astar_int2d_weighted,
+    door::Door,
+    group::{heading, Group, GroupDemand},
     object::{Object, ObjectType},
     pedestrian::Pedestrian,
+    poi::DestinationSampler,
+    profile::{choose_profile, Profile},
 };
 
 use crate::{DISCRETIZATION, TOROIDAL};
@@ -12,7 +17,7 @@ use krabmaga::{
         fields::{field::Field, field_2d::Field2D, sparse_number_grid_2d::SparseNumberGrid2D},
         location::{Int2D, Real2D},
     },
-    rand::{self, Rng},
+    rand::Rng,
 };
 use ndarray::Array2;
 use std::collections::HashMap;
@@ -66,6 +71,8 @@ pub fn make_peds(
     num_peds: u32,
     dim: (f32, f32),
     obj_grid: &SparseNumberGrid2D<u8>,
+    destinations: Option<&DestinationSampler>,
+    rng: &mut impl Rng,
 ) -> Vec<Pedestrian> {
     // Gather list of available positions
 
@@ -84,25 +91,274 @@ pub fn make_peds(
     );
 
     let mut pedestrians = Vec::<Pedestrian>::new();
-    let mut rng = rand::thread_rng();
+    //Destinations chosen at each point of interest
+    let mut poi_counts = vec![0; destinations.map_or(0, |d| d.pois().len())];
 
     for i in 0..num_peds {
         let _speed: f32 = rng.gen_range(1.0..5.0);
         let last_d = Real2D { x: 0., y: 0. };
         let loc = available_positions[rng.gen_range(0..available_positions.len())];
-        let dest = Some(available_positions[rng.gen_range(0..available_positions.len())]);
+        let dest = match destinations {
+            Some(destinations) => {
+                let (poi, dest) = destinations.draw(loc, rng);
+                poi_counts[poi] += 1;
+                Some(dest)
+            }
+            None => Some(available_positions[rng.gen_range(0..available_positions.len())]),
+        };
 
         pedestrians.push(Pedestrian::new(i, loc, last_d, dest, 1.0));
     }
+    if let Some(destinations) = destinations {
+        for (poi, count) in destinations.pois().iter().zip(poi_counts) {
+            println!("{} Destinations at {} ({})", count, poi.name, poi.layer);
+        }
+    }
     pedestrians
 }
 
-//Pre-compute shortest paths for agents, and place in ped_paths
+//Draw a profile for every pedestrian by the profiles' shares, with its free speed converted to
+//cells per step and its body radius
+pub fn assign_profiles(
+    peds: &mut [Pedestrian],
+    profiles: &[Profile],
+    cell_size: f32,
+    step_duration: f32,
+    rng: &mut impl Rng,
+) {
+    if profiles.is_empty() {
+        return;
+    }
+    let mut counts = vec![0; profiles.len()];
+    for ped in peds.iter_mut() {
+        let profile_idx = choose_profile(profiles, rng.gen());
+        let profile = &profiles[profile_idx];
+        ped.profile = profile_idx;
+        ped.speed = profile.free_speed.sample(rng).max(0.) * step_duration / cell_size;
+        ped.radius = profile.radius.sample(rng).max(0.);
+        counts[profile_idx] += 1;
+    }
+    for (profile, count) in profiles.iter().zip(counts) {
+        println!("{} Pedestrians with profile {}", count, profile.name);
+    }
+}
+
+//Draw an activity chain for every pedestrian by the chains' shares and a free cell and dwell
+//time at each of its stops. Pedestrians with a chain head for its first stop, and keep their own
+//destination for the last leg.
+pub fn make_itineraries(
+    peds: &mut [Pedestrian],
+    chains: &[ActivityChain],
+    obj_grid: &SparseNumberGrid2D<u8>,
+    step_duration: f32,
+    rng: &mut impl Rng,
+) -> HashMap<u32, Itinerary> {
+    let mut itineraries = HashMap::new();
+    if chains.is_empty() {
+        return itineraries;
+    }
+    //Free cells of every stop, by chain
+    let stop_cells: Vec<Vec<Vec<Real2D>>> = chains
+        .iter()
+        .map(|chain| {
+            chain
+                .stops
+                .iter()
+                .map(|stop| stop.region.free_cells(obj_grid))
+                .collect()
+        })
+        .collect();
+
+    let mut counts = vec![0; chains.len()];
+    for ped in peds.iter_mut() {
+        let Some(chain_idx) = choose_chain(chains, rng.gen()) else {
+            continue;
+        };
+        let Some(dest) = ped.dest else {
+            continue;
+        };
+        let mut legs = Vec::<Leg>::new();
+        for (stop, cells) in chains[chain_idx].stops.iter().zip(&stop_cells[chain_idx]) {
+            //Stops without a free cell are left out
+            if cells.is_empty() {
+                continue;
+            }
+            let cell = cells[rng.gen_range(0..cells.len())];
+            let dwell = stop.dwell.sample(rng).max(0.) / step_duration;
+            legs.push(Leg::new(cell, dwell.round() as u64));
+        }
+        legs.push(Leg::new(dest, 0));
+
+        ped.dest = Some(legs[0].dest());
+        (ped.dir_x, ped.dir_y) = normalize_motion_vector(ped.loc, legs[0].dest());
+        itineraries.insert(
+            ped.id,
+            Itinerary {
+                chain: chain_idx,
+                legs,
+                leg: 0,
+            },
+        );
+        counts[chain_idx] += 1;
+    }
+    for (chain, count) in chains.iter().zip(counts) {
+        println!("{} Pedestrians on activity chain {}", count, chain.name);
+    }
+    itineraries
+}
+
+//Free cell nearest to a point, searching outwards up to max_radius cells
+pub fn nearest_free_cell(
+    obj_grid: &SparseNumberGrid2D<u8>,
+    target: Real2D,
+    max_radius: i32,
+) -> Option<Real2D> {
+    let (x, y) = (target.x.round() as i32, target.y.round() as i32);
+    (0..=max_radius).find_map(|radius| {
+        iproduct!(-radius..=radius, -radius..=radius)
+            .filter(|(dx, dy)| dx.abs().max(dy.abs()) == radius)
+            .map(|(dx, dy)| Int2D {
+                x: x + dx,
+                y: y + dy,
+            })
+            .filter(|cell| {
+                cell.x >= 0
+                    && cell.y >= 0
+                    && cell.x < obj_grid.width
+                    && cell.y < obj_grid.height
+                    && obj_grid.get_value(cell).is_none()
+            })
+            .min_by(|a, b| {
+                let distance =
+                    |cell: &Int2D| (cell.x as f32 - target.x).hypot(cell.y as f32 - target.y);
+                distance(a).total_cmp(&distance(b))
+            })
+            .map(|cell| Real2D {
+                x: cell.x as f32,
+                y: cell.y as f32,
+            })
+    })
+}
+
+//Split the population into groups of sizes drawn from the demand. The first pedestrian of a group
+//leads; the others are moved next to it in formation, share its destination and all walk at the
+//speed of the slowest.
+pub fn make_groups(
+    peds: &mut [Pedestrian],
+    demand: &GroupDemand,
+    obj_grid: &SparseNumberGrid2D<u8>,
+    rng: &mut impl Rng,
+) -> Vec<Group> {
+    let mut groups = Vec::<Group>::new();
+    let mut next = 0;
+    while next < peds.len() {
+        let size = demand.size(rng.gen()).clamp(1, peds.len() - next);
+        if size > 1 {
+            let leader = peds[next];
+            let mut group = Group {
+                leader: leader.id,
+                members: Vec::new(),
+                offsets: Vec::new(),
+            };
+            let facing = heading(Real2D {
+                x: leader.dir_x,
+                y: leader.dir_y,
+            })
+            .unwrap_or((1., 0.));
+            let speed = peds[next..next + size]
+                .iter()
+                .map(|ped| ped.speed)
+                .fold(f32::INFINITY, f32::min);
+
+            for k in 1..size {
+                group.members.push(peds[next + k].id);
+                group.offsets.push(demand.formation.offset(k));
+                let start = group
+                    .slot(peds[next + k].id, leader.loc, facing)
+                    .and_then(|slot| nearest_free_cell(obj_grid, slot, demand.max_gap))
+                    .unwrap_or(leader.loc);
+                let member = &mut peds[next + k];
+                member.loc = start;
+                member.dest = leader.dest;
+                (member.dir_x, member.dir_y) = (leader.dir_x, leader.dir_y);
+            }
+            for ped in peds[next..next + size].iter_mut() {
+                ped.speed = speed;
+            }
+            groups.push(group);
+        }
+        next += size;
+    }
+    println!(
+        "{} Groups with {} members formed",
+        groups.len(),
+        groups
+            .iter()
+            .map(|group| group.members.len() + 1)
+            .sum::<usize>()
+    );
+    groups
+}
+
+//Shortest path on the obstacle grid from origin to dest, as Real2D positions on the field.
+//One-way doors may only be passed in their direction.
+pub fn plan_path(
+    origin: Real2D,
+    dest: Real2D,
+    obj_grid: &SparseNumberGrid2D<u8>,
+    doors: &[Door],
+) -> Result<Vec<Real2D>, anyhow::Error> {
+    plan_weighted_path(origin, dest, obj_grid, doors, &|_| Some(1.))
+}
+
+//Cheapest path when stepping onto a cell costs cell_cost(cell) moves, avoiding cells without a cost
+pub fn plan_weighted_path(
+    origin: Real2D,
+    dest: Real2D,
+    obj_grid: &SparseNumberGrid2D<u8>,
+    doors: &[Door],
+    cell_cost: &dyn Fn(&Int2D) -> Option<f32>,
+) -> Result<Vec<Real2D>, anyhow::Error> {
+    let move_cost =
+        |from: &Int2D, to: &Int2D| match doors.iter().any(|door| door.forbids_move(from, to)) {
+            true => None,
+            false => cell_cost(to),
+        };
+
+    astar_int2d_weighted(
+        &Int2D {
+            x: origin.x as i32,
+            y: origin.y as i32,
+        },
+        &Int2D {
+            x: dest.x as i32,
+            y: dest.y as i32,
+        },
+        obj_grid,
+        &move_cost,
+    )
+    .map(|shortest_path| {
+        shortest_path
+            .into_iter()
+            .map(|node| Real2D {
+                x: node.x as f32,
+                y: node.y as f32,
+            })
+            .collect()
+    })
+}
+
+//Plans a pedestrian's route from origin to dest, as positions on the field
+pub type RoutePlanner<'a> =
+    dyn Fn(&Pedestrian, Real2D, Real2D) -> Result<Vec<Real2D>, anyhow::Error> + 'a;
+
+//Pre-compute paths for agents, and place in ped_paths
 // In this case, we should convert vector of Int2D to Real2D, since we will use these
 // values as positions for our agents on a real field
+// Each path is planned by plan_route(pedestrian, origin, dest)
 pub fn make_paths(
     pedestrians: &Vec<Pedestrian>,
-    obj_grid: &SparseNumberGrid2D<u8>,
+    plan_route: &RoutePlanner<'_>,
 ) -> HashMap<u32, std::vec::IntoIter<Real2D>> {
     let mut ped_path_map = HashMap::<u32, std::vec::IntoIter<Real2D>>::new();
     let mut failed_path_ids = Vec::<u32>::new();
@@ -111,29 +367,8 @@ pub fn make_paths(
         let Pedestrian { id, loc, dest, .. } = ped;
 
         if let Some(this_dest) = dest {
-            let possible_path =
-                NavigationPoint::<i32, Int2D, SparseNumberGrid2D<u8>>::path_to_destination(
-                    &Int2D {
-                        x: loc.x as i32,
-                        y: loc.y as i32,
-                    },
-                    &Int2D {
-                        x: this_dest.x as i32,
-                        y: this_dest.y as i32,
-                    },
-                    obj_grid,
-                );
-
-            match possible_path {
-                Ok(shortest_path) => {
-                    let real_vec: Vec<Real2D> = shortest_path
-                        .into_iter()
-                        .map(|node| Real2D {
-                            x: node.x as f32,
-                            y: node.y as f32,
-                        })
-                        .collect();
-
+            match plan_route(ped, *loc, *this_dest) {
+                Ok(real_vec) => {
                     ped_path_map.insert(*id, real_vec.into_iter());
                 }
                 Err(e) => {
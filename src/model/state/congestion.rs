@@ -0,0 +1,84 @@
+use crate::model::{
+    pedestrian::{PedStatus, Pedestrian},
+    state::state::ModelState,
+};
+
+use krabmaga::engine::location::{Int2D, Real2D};
+
+impl ModelState {
+    /// Let the pedestrians due to look around replan if their route ahead is crowded.
+    /// Each pedestrian looks every `interval` steps, staggered by id to spread the work.
+    pub fn reroute_congested(&mut self) {
+        let Some(congestion) = self.congestion.clone() else {
+            return;
+        };
+        let interval = congestion.interval.max(1);
+        let due: Vec<Pedestrian> = self
+            .active_peds
+            .values()
+            .filter(|ped| {
+                (self.step + ped.id as u64).is_multiple_of(interval)
+                    && ped.status != PedStatus::Arrived
+                    && !self.dwelling.contains_key(&ped.id)
+                    && !self.stranded_peds.contains(&ped.id)
+                    //Group members keep to their leader
+                    && self
+                        .group_of
+                        .get(&ped.id)
+                        .is_none_or(|idx| self.groups[*idx].leader == ped.id)
+            })
+            .copied()
+            .collect();
+        if due.is_empty() {
+            return;
+        }
+
+        let density = self.step_density();
+        let density_at = |cell: &Int2D| {
+            density
+                .get([cell.y as usize, cell.x as usize])
+                .copied()
+                .unwrap_or(0.)
+        };
+        let mut rerouted = Vec::<(u32, Vec<Real2D>)>::new();
+        for ped in due.iter() {
+            let (Some(dest), Some(path)) = (ped.dest, self.ped_paths.get(&ped.id)) else {
+                continue;
+            };
+            let seen = |cell: &Int2D| {
+                (cell.x as f32 - ped.loc.x).hypot(cell.y as f32 - ped.loc.y) * self.cell_size
+                    <= congestion.perception_radius
+            };
+
+            let delay_steps: f32 = path
+                .as_slice()
+                .iter()
+                .map(|point| Int2D {
+                    x: point.x as i32,
+                    y: point.y as i32,
+                })
+                .filter(|cell| seen(cell))
+                .map(|cell| congestion.cell_delay(density_at(&cell), ped.speed))
+                .sum();
+            if delay_steps * self.step_duration <= congestion.delay_threshold {
+                continue;
+            }
+
+            match self.plan_route_weighted(ped, ped.loc, dest, &|cell| match seen(cell) {
+                true => congestion.cost_factor(density_at(cell)),
+                false => 1.,
+            }) {
+                Ok(new_path) => rerouted.push((ped.id, new_path)),
+                Err(e) => println!(
+                    "Pedestrian {} cannot reroute around the crowd: {}",
+                    ped.id, e
+                ),
+            }
+        }
+
+        for (id, new_path) in rerouted {
+            self.ped_paths.insert(id, new_path.into_iter());
+            self.count_replan(id);
+        }
+    }
+}
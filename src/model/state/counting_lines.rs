@@ -0,0 +1,119 @@
+use crate::model::{counting_line::LineCrossing, state::state::ModelState};
+use crate::system_interface::output_writer::{output_path, write_csv};
+
+use krabmaga::engine::location::Real2D;
+
+impl ModelState {
+    /// Record every counting line crossed by a pedestrian's move in the step just taken
+    pub fn record_line_crossings(&mut self) {
+        if self.counting_lines.is_empty() {
+            return;
+        }
+
+        let mut crossings = Vec::<LineCrossing>::new();
+        for ped in self.active_peds.values() {
+            if ped.last_d.x == 0. && ped.last_d.y == 0. {
+                continue;
+            }
+            let from = Real2D {
+                x: ped.loc.x - ped.last_d.x,
+                y: ped.loc.y - ped.last_d.y,
+            };
+            for (line_idx, line) in self.counting_lines.iter().enumerate() {
+                if let Some(positive) = line.crossing(&from, &ped.loc) {
+                    crossings.push(LineCrossing {
+                        line: line_idx,
+                        step: self.step,
+                        id: ped.id,
+                        positive,
+                    });
+                }
+            }
+        }
+        crossings.sort_by_key(|crossing| (crossing.line, crossing.id));
+        self.line_crossings.extend(crossings);
+    }
+
+    /// Write every crossing, and the flow, specific flow and mean time headway per line,
+    /// direction and aggregation interval
+    pub fn write_line_counts(&self) {
+        if self.counting_lines.is_empty() {
+            return;
+        }
+
+        let crossing_rows = self.line_crossings.iter().map(|crossing| {
+            format!(
+                "{},{},{},{},{}",
+                self.counting_lines[crossing.line].name,
+                crossing.step,
+                crossing.step as f32 * self.step_duration,
+                crossing.id,
+                if crossing.positive {
+                    "positive"
+                } else {
+                    "negative"
+                }
+            )
+        });
+        if let Err(e) = output_path(
+            &self.output_dir,
+            &format!("counts_{}_crossings.csv", self.run),
+        )
+        .and_then(|path| write_csv(&path, "line,step,time,id,direction", crossing_rows))
+        {
+            println!("Failed to write line crossings: {}", e);
+        }
+
+        //Crossings are stamped with the number of steps taken, so bins are (from_step, to_step]
+        let interval = self.count_interval.max(1);
+        let mut flow_rows = Vec::<String>::new();
+        for (line_idx, line) in self.counting_lines.iter().enumerate() {
+            let width = line.length() * self.cell_size;
+            for from_step in (0..self.step.max(1)).step_by(interval as usize) {
+                let to_step = (from_step + interval).min(self.step.max(1));
+                let duration = (to_step - from_step) as f32 * self.step_duration;
+                for positive in [true, false] {
+                    let times: Vec<f32> = self
+                        .line_crossings
+                        .iter()
+                        .filter(|crossing| {
+                            crossing.line == line_idx
+                                && crossing.positive == positive
+                                && crossing.step > from_step
+                                && crossing.step <= to_step
+                        })
+                        .map(|crossing| crossing.step as f32 * self.step_duration)
+                        .collect();
+                    let flow = times.len() as f32 / duration;
+                    let mean_headway = if times.len() > 1 {
+                        (times[times.len() - 1] - times[0]) / (times.len() - 1) as f32
+                    } else {
+                        f32::NAN
+                    };
+                    flow_rows.push(format!(
+                        "{},{},{},{},{},{},{},{}",
+                        line.name,
+                        from_step as f32 * self.step_duration,
+                        to_step as f32 * self.step_duration,
+                        if positive { "positive" } else { "negative" },
+                        times.len(),
+                        flow,
+                        flow / width,
+                        mean_headway
+                    ));
+                }
+            }
+        }
+        if let Err(e) = output_path(&self.output_dir, &format!("counts_{}_flows.csv", self.run))
+            .and_then(|path| {
+                write_csv(
+                    &path,
+                    "line,from_time,to_time,direction,count,flow,specific_flow,mean_headway",
+                    flow_rows,
+                )
+            })
+        {
+            println!("Failed to write line flows: {}", e);
+        }
+    }
+}
@@ -0,0 +1,78 @@
+use crate::model::{
+    crossing::{CrossingStats, SignalPhase},
+    state::state::ModelState,
+};
+
+use krabmaga::{
+    engine::location::{Int2D, Real2D},
+    rand::Rng,
+};
+
+impl ModelState {
+    /// Clear the crossing counts, waits and violations of the previous run
+    pub fn reset_crossings(&mut self) {
+        self.crossing_stats = vec![CrossingStats::default(); self.crossings.len()];
+        self.crossing_waits.clear();
+    }
+
+    /// Index of the crossing a move from `loc` onto `next` steps onto from the kerb
+    pub fn crossing_entered(&self, loc: Real2D, next: Real2D) -> Option<usize> {
+        let loc_cell = Int2D {
+            x: loc.x as i32,
+            y: loc.y as i32,
+        };
+        let next_cell = Int2D {
+            x: next.x as i32,
+            y: next.y as i32,
+        };
+        self.crossings.iter().position(|crossing| {
+            crossing.region.contains(&next_cell) && !crossing.region.contains(&loc_cell)
+        })
+    }
+
+    /// Whether the signal of a crossing lets pedestrians on this step
+    pub fn crossing_open(&self, idx: usize) -> bool {
+        self.crossings[idx].signal.phase_at(self.step) == SignalPhase::Green
+    }
+
+    /// Whether a pedestrian at the kerb of a crossing on flashing or red crosses anyway.
+    /// The decision is taken once, on arrival at the kerb; those who stay start waiting.
+    pub fn crosses_against_signal(&mut self, id: u32, idx: usize) -> bool {
+        if self.crossing_waits.contains_key(&id) {
+            return false;
+        }
+        let violation_probability = self.crossings[idx].violation_probability;
+        if violation_probability > 0. && self.rng.gen_bool(violation_probability.min(1.)) {
+            return true;
+        }
+        self.crossing_waits.insert(id, (idx, self.step));
+        false
+    }
+
+    /// Count a pedestrian onto a crossing, with the wait it had at the kerb
+    pub fn pass_crossing(&mut self, id: u32, idx: usize, violation: bool) {
+        if let Some((_, wait_start)) = self.crossing_waits.remove(&id) {
+            self.crossing_stats[idx]
+                .wait_steps
+                .push(self.step - wait_start);
+        }
+        if violation {
+            self.crossing_stats[idx].violations += 1;
+        }
+        self.crossing_stats[idx].crossings += 1;
+    }
+
+    pub fn report_crossings(&self) {
+        for (crossing, stats) in self.crossings.iter().zip(self.crossing_stats.iter()) {
+            println!(
+                "Crossing {}: {} crossings, {} red-light violations, {} waited (mean {:.1} steps, max {} steps)",
+                crossing.name,
+                stats.crossings,
+                stats.violations,
+                stats.wait_steps.len(),
+                stats.mean_wait(),
+                stats.max_wait()
+            );
+        }
+    }
+}
@@ -0,0 +1,134 @@
+use crate::model::{
+    calc_utils::density::{kernel_density, voronoi_density, DensityMethod},
+    state::state::ModelState,
+};
+use crate::system_interface::{
+    output_writer::output_path,
+    raster_writer::{write_geotiff, write_png_heatmap},
+};
+
+use krabmaga::engine::location::Real2D;
+use ndarray::Array2;
+
+impl ModelState {
+    pub fn grid_dim(&self) -> (usize, usize) {
+        (self.obj_grid.height as usize, self.obj_grid.width as usize)
+    }
+
+    /// Density in persons/m² on the obstacle grid for the pedestrians currently on the field
+    pub fn current_density(&self) -> Array2<f32> {
+        let positions: Vec<Real2D> = self.active_peds.values().map(|ped| ped.loc).collect();
+        let output = self.density_output.as_ref();
+        let method = output.map_or(DensityMethod::Kernel, |output| output.method);
+        let bandwidth = output.map_or(1.0, |output| output.bandwidth);
+
+        match method {
+            DensityMethod::Kernel => {
+                kernel_density(&positions, self.grid_dim(), self.cell_size, bandwidth)
+            }
+            DensityMethod::Voronoi => {
+                voronoi_density(&positions, self.grid_dim(), self.cell_size, bandwidth)
+            }
+        }
+    }
+
+    /// Density for where the pedestrians stood after the last step, computed once per step
+    pub fn step_density(&mut self) -> Array2<f32> {
+        match &self.density_cache {
+            Some((step, density)) if *step == self.step => density.clone(),
+            _ => {
+                let density = self.current_density();
+                self.density_cache = Some((self.step, density.clone()));
+                density
+            }
+        }
+    }
+
+    /// Add the current density to the cumulative map and the running time window
+    pub fn record_density(&mut self) {
+        let Some(output) = &self.density_output else {
+            return;
+        };
+        let (interval, window) = (output.interval.max(1), output.window);
+        if self.step.is_multiple_of(interval) {
+            let density = self.step_density();
+            self.density_sum += &density;
+            self.density_samples += 1;
+            self.window_density_sum += &density;
+            self.window_density_samples += 1;
+        }
+
+        //Windows end on their own boundaries, whether or not the step was sampled
+        if let Some(window) = window {
+            if window > 0 && self.step.is_multiple_of(window) {
+                self.close_density_window();
+            }
+        }
+    }
+
+    fn close_density_window(&mut self) {
+        //A window too short to hold a sample has no map, but the next one still starts here
+        if self.window_density_samples > 0 {
+            let mean = &self.window_density_sum / self.window_density_samples as f32;
+            self.density_windows.push((self.window_start, mean));
+        }
+        self.window_density_sum.fill(0.);
+        self.window_density_samples = 0;
+        self.window_start = self.step;
+    }
+
+    /// Write the mean density over the run, and over each time window, as PNG and GeoTIFF
+    pub fn write_density_maps(&mut self) {
+        let Some(output) = self.density_output.clone() else {
+            return;
+        };
+        if output.window.is_some() {
+            self.close_density_window();
+        }
+
+        let mut maps = Vec::<(String, Array2<f32>)>::new();
+        if self.density_samples > 0 {
+            maps.push((
+                format!("density_{}_mean", self.run),
+                &self.density_sum / self.density_samples as f32,
+            ));
+        }
+        for (start, mean) in self.density_windows.iter() {
+            maps.push((
+                format!("density_{}_from_step_{}", self.run, start),
+                mean.clone(),
+            ));
+        }
+
+        let navigable = self.raster_navigable();
+        for (name, map) in maps {
+            if output.png {
+                if let Err(e) = output_path(&self.output_dir, &format!("{}.png", name))
+                    .map_err(anyhow::Error::from)
+                    .and_then(|path| write_png_heatmap(&path, &map, output.max_density, &navigable))
+                {
+                    println!("Failed to write density map {}: {}", name, e);
+                }
+            }
+            if output.geotiff {
+                if let Err(e) = output_path(&self.output_dir, &format!("{}.tif", name))
+                    .map_err(anyhow::Error::from)
+                    .and_then(|path| write_geotiff(&path, &map, self.cell_size))
+                {
+                    println!("Failed to write density map {}: {}", name, e);
+                }
+            }
+        }
+    }
+
+    pub fn reset_density(&mut self) {
+        let dim = self.grid_dim();
+        self.density_sum = Array2::zeros(dim);
+        self.density_samples = 0;
+        self.window_density_sum = Array2::zeros(dim);
+        self.window_density_samples = 0;
+        self.window_start = 0;
+        self.density_windows.clear();
+        self.density_cache = None;
+    }
+}
@@ -0,0 +1,87 @@
+use crate::model::state::state::ModelState;
+
+use krabmaga::engine::location::{Int2D, Real2D};
+
+impl ModelState {
+    /// Top up every door with the passages its flow capacity allows for this step
+    pub fn refill_doors(&mut self) {
+        let step = self.step;
+        for (door, door_state) in self.doors.iter().zip(self.door_states.iter_mut()) {
+            let capacity = door.capacity_per_step(self.cell_size, self.step_duration);
+            door_state.tokens = (door_state.tokens + capacity).min(capacity.max(1.));
+
+            //Pedestrians that did not try the door last step have left the queue, rerouted
+            //or gone, and no longer hold up those behind them
+            door_state.queue.retain(|(_, tried)| tried + 1 >= step);
+        }
+    }
+
+    /// Index of the door a move from `loc` onto `next` steps into
+    pub fn door_entered(&self, loc: Real2D, next: Real2D) -> Option<usize> {
+        let loc_cell = Int2D {
+            x: loc.x as i32,
+            y: loc.y as i32,
+        };
+        let next_cell = Int2D {
+            x: next.x as i32,
+            y: next.y as i32,
+        };
+        self.doors
+            .iter()
+            .position(|door| door.region.contains(&next_cell) && !door.region.contains(&loc_cell))
+    }
+
+    /// Whether a door has a passage left this step for a pedestrian. Those queueing in front
+    /// of it go first come, first served: the passages left go to the head of the queue, and a
+    /// pedestrian not in it comes after everyone who is.
+    pub fn door_open(&self, id: u32, idx: usize) -> bool {
+        let door_state = &self.door_states[idx];
+        let place = door_state
+            .queue
+            .iter()
+            .position(|(queued, _)| *queued == id)
+            .unwrap_or(door_state.queue.len());
+        door_state.tokens >= (place + 1) as f32
+    }
+
+    /// Put a pedestrian held at a door at the back of its queue, or keep its place if it is
+    /// already in it, and track the longest queue seen in front of the door
+    pub fn join_door_queue(&mut self, id: u32, idx: usize) {
+        let step = self.step;
+        let door_state = &mut self.door_states[idx];
+        match door_state
+            .queue
+            .iter_mut()
+            .find(|(queued, _)| *queued == id)
+        {
+            Some((_, tried)) => *tried = step,
+            None => door_state.queue.push_back((id, step)),
+        }
+        door_state.max_queue = door_state.max_queue.max(door_state.queue.len());
+    }
+
+    /// Let a pedestrian through a door, using up one of its passages
+    pub fn pass_door(&mut self, id: u32, idx: usize) {
+        let door_state = &mut self.door_states[idx];
+        door_state.tokens -= 1.;
+        door_state.passages += 1;
+        door_state.queue.retain(|(queued, _)| *queued != id);
+    }
+
+    pub fn report_doors(&self) {
+        let elapsed = self.step as f32 * self.step_duration;
+        for (door, door_state) in self.doors.iter().zip(self.door_states.iter()) {
+            println!(
+                "Door {}: {} passages ({:.2} persons/s), longest queue {}",
+                door.name,
+                door_state.passages,
+                if elapsed > 0. {
+                    door_state.passages as f32 / elapsed
+                } else {
+                    0.
+                },
+                door_state.max_queue
+            );
+        }
+    }
+}
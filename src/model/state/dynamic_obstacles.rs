@@ -0,0 +1,172 @@
+use std::collections::HashSet;
+
+use crate::model::{pedestrian::Pedestrian, scenario::Region, state::state::ModelState};
+
+use krabmaga::engine::{
+    fields::{field::Field, sparse_number_grid_2d::SparseNumberGrid2D},
+    location::{Int2D, Real2D},
+};
+use ndarray::Array2;
+
+impl ModelState {
+    /// Close and reopen the scheduled obstacle regions for the current step, and replan
+    /// any pedestrian whose remaining path runs through a newly closed cell.
+    pub fn apply_obstacle_events(&mut self) {
+        let mut closed_cells = HashSet::<Int2D>::new();
+        let mut reopened = false;
+
+        for (idx, event) in self.obstacle_events.iter().enumerate() {
+            let active = event.is_active(self.step);
+            let applied = self.applied_closures.contains_key(&idx);
+
+            if active && !applied {
+                let newly_closed: Vec<Int2D> = event
+                    .region
+                    .cells()
+                    .filter(|cell| {
+                        cell.x >= 0
+                            && cell.y >= 0
+                            && cell.x < self.obj_grid.width
+                            && cell.y < self.obj_grid.height
+                            && !self.raster_obstacles.contains(cell)
+                    })
+                    .collect();
+                closed_cells.extend(newly_closed.iter().copied());
+                self.applied_closures.insert(idx, newly_closed);
+            } else if !active && applied {
+                self.applied_closures.remove(&idx);
+                reopened = true;
+            }
+        }
+
+        if closed_cells.is_empty() && !reopened {
+            return;
+        }
+        self.rebuild_obj_grid();
+
+        let mut affected: Vec<u32> = self
+            .ped_paths
+            .iter()
+            .filter(|(_, path)| {
+                path.as_slice().iter().any(|point| {
+                    closed_cells.contains(&Int2D {
+                        x: point.x as i32,
+                        y: point.y as i32,
+                    })
+                })
+            })
+            .map(|(id, _)| *id)
+            .collect();
+
+        if reopened {
+            affected.extend(self.stranded_peds.iter());
+        }
+
+        //Evacuees follow the exits' distance fields, which the change has made stale
+        match self.evacuation {
+            Some(_) => self.reroute_evacuees(&affected),
+            None => self.replan_paths(&affected, &closed_cells),
+        }
+    }
+
+    /// Remove every applied closure from the obstacle grid, as at the start of a run
+    pub fn reopen_closures(&mut self) {
+        self.applied_closures.clear();
+        self.rebuild_obj_grid();
+    }
+
+    /// Rebuild the obstacle grid from the raster and the closures in force. The grid is
+    /// never edited in place: its `update` keeps only what was written since the last one.
+    pub fn rebuild_obj_grid(&mut self) {
+        let mut obj_grid = SparseNumberGrid2D::new(self.obj_grid.width, self.obj_grid.height);
+        for cell in self
+            .raster_obstacles
+            .iter()
+            .chain(self.applied_closures.values().flatten())
+        {
+            obj_grid.set_value_location(0, cell);
+        }
+        obj_grid.update();
+        self.obj_grid = obj_grid;
+    }
+
+    /// Raster as 1 for free cells and 0 for obstacles, indexed [[row, col]], without the
+    /// closures: the walkable area that outputs are drawn and measured over
+    pub fn raster_navigable(&self) -> Array2<u8> {
+        let (width, height) = (self.obj_grid.width, self.obj_grid.height);
+        Array2::from_shape_fn((height as usize, width as usize), |(row, col)| {
+            let cell = Int2D {
+                x: col as i32,
+                y: row as i32,
+            };
+            match self.raster_obstacles.contains(&cell) {
+                true => 0,
+                false => 1,
+            }
+        })
+    }
+
+    /// Cells of a region on the grid and free of raster obstacles, as positions on the field.
+    /// Closures are left out of account, so measured areas keep their size over the run.
+    pub fn raster_free_cells(&self, region: &Region) -> Vec<Real2D> {
+        region
+            .cells()
+            .filter(|cell| {
+                cell.x >= 0
+                    && cell.y >= 0
+                    && cell.x < self.obj_grid.width
+                    && cell.y < self.obj_grid.height
+                    && !self.raster_obstacles.contains(cell)
+            })
+            .map(|cell| Real2D {
+                x: cell.x as f32,
+                y: cell.y as f32,
+            })
+            .collect()
+    }
+
+    /// Where the pedestrian stood at the end of the last step
+    pub fn current_location(&self, ped: &Pedestrian) -> Real2D {
+        match self.active_peds.get(&ped.id) {
+            Some(current) => current.loc,
+            None => ped.loc,
+        }
+    }
+
+    fn replan_paths(&mut self, ids: &[u32], closed_cells: &HashSet<Int2D>) {
+        for ped in self.peds.iter().filter(|ped| ids.contains(&ped.id)) {
+            let Some(dest) = ped.dest else {
+                continue;
+            };
+            let loc = self.current_location(ped);
+
+            match self.plan_route(ped, loc, dest) {
+                Ok(new_path) => {
+                    self.ped_paths.insert(ped.id, new_path.into_iter());
+                    self.stranded_peds.remove(&ped.id);
+                    if let Some(trip) = self.trips.get_mut(&ped.id) {
+                        trip.replans += 1;
+                    }
+                }
+                Err(_) => {
+                    //No way around the closure: walk up to it and wait there until something reopens
+                    if let Some(path) = self.ped_paths.get_mut(&ped.id) {
+                        let waiting_path: Vec<Real2D> = path
+                            .as_slice()
+                            .iter()
+                            .take_while(|point| {
+                                !closed_cells.contains(&Int2D {
+                                    x: point.x as i32,
+                                    y: point.y as i32,
+                                })
+                            })
+                            .copied()
+                            .collect();
+                        *path = waiting_path.into_iter();
+                    }
+                    self.stranded_peds.insert(ped.id);
+                }
+            }
+        }
+    }
+}
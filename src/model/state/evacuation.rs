@@ -0,0 +1,225 @@
+use std::collections::HashSet;
+
+use crate::model::{
+    calc_utils::distance_field::{descend, distance_at, distance_field, UNREACHABLE},
+    state::state::ModelState,
+};
+use crate::system_interface::output_writer::{output_path, write_csv};
+
+use krabmaga::engine::location::{Int2D, Real2D};
+use ndarray::Array2;
+
+fn to_cell(loc: Real2D) -> Int2D {
+    Int2D {
+        x: loc.x as i32,
+        y: loc.y as i32,
+    }
+}
+
+impl ModelState {
+    //Distance to each exit over the current obstacle grid, keeping to one-way doors
+    fn exit_distance_fields(&self) -> Vec<Array2<u32>> {
+        let Some(evacuation) = &self.evacuation else {
+            return Vec::new();
+        };
+        let forbids =
+            |from: &Int2D, to: &Int2D| self.doors.iter().any(|door| door.forbids_move(from, to));
+        evacuation
+            .exits
+            .iter()
+            .map(|exit| distance_field(exit.region.cells(), &self.obj_grid, &forbids))
+            .collect()
+    }
+
+    /// Send every pedestrian to the exit nearest to it over the obstacle grid.
+    /// Pedestrians with no way out wait where they are, and count as remaining inside.
+    pub fn assign_nearest_exits(&mut self) {
+        if self.evacuation.is_none() {
+            return;
+        }
+        self.exit_distances = self.exit_distance_fields();
+
+        let mut unreachable = 0;
+        for ped_idx in 0..self.peds.len() {
+            let cell = to_cell(self.peds[ped_idx].loc);
+            match self.nearest_exit(&cell, &[]) {
+                Some(exit_idx) => self.route_to_exit(ped_idx, exit_idx, &cell),
+                None => {
+                    self.strand_evacuee(ped_idx);
+                    unreachable += 1;
+                }
+            }
+        }
+        println!("{} Pedestrians cannot reach any exit", unreachable);
+    }
+
+    /// Recompute the exit distances after the obstacle grid has changed, and send the given
+    /// pedestrians to the exit now nearest to them
+    pub fn reroute_evacuees(&mut self, ids: &[u32]) {
+        self.exit_distances = self.exit_distance_fields();
+        let evacuated: HashSet<u32> = self.evacuated.iter().map(|(id, _, _)| *id).collect();
+        for ped_idx in 0..self.peds.len() {
+            let id = self.peds[ped_idx].id;
+            if !ids.contains(&id) || evacuated.contains(&id) {
+                continue;
+            }
+            let cell = to_cell(self.current_location(&self.peds[ped_idx]));
+            match self.nearest_exit(&cell, &[]) {
+                Some(exit_idx) => {
+                    self.route_to_exit(ped_idx, exit_idx, &cell);
+                    self.stranded_peds.remove(&id);
+                    self.count_replan(id);
+                }
+                None => self.strand_evacuee(ped_idx),
+            }
+        }
+    }
+
+    //Hold a pedestrian with no way out where it stands until something reopens
+    fn strand_evacuee(&mut self, ped_idx: usize) {
+        let id = self.peds[ped_idx].id;
+        self.peds[ped_idx].dest = None;
+        self.ped_paths.insert(id, Vec::new().into_iter());
+        self.stranded_peds.insert(id);
+    }
+
+    /// Let pedestrians switch to a less congested exit when it is cheaper overall
+    pub fn update_exit_choices(&mut self) {
+        let Some(evacuation) = &self.evacuation else {
+            return;
+        };
+        let (radius, weight) = (evacuation.congestion_radius, evacuation.congestion_weight);
+
+        let evacuated: HashSet<u32> = self.evacuated.iter().map(|(id, _, _)| *id).collect();
+        let positions: Vec<(usize, Int2D)> = self
+            .peds
+            .iter()
+            .enumerate()
+            .filter(|(_, ped)| {
+                self.exit_choice.contains_key(&ped.id) && !evacuated.contains(&ped.id)
+            })
+            .map(|(ped_idx, ped)| (ped_idx, to_cell(self.current_location(ped))))
+            .collect();
+
+        let crowding: Vec<f32> = self
+            .exit_distances
+            .iter()
+            .map(|distances| {
+                let crowd = positions
+                    .iter()
+                    .filter(|(_, cell)| distance_at(distances, cell) <= radius)
+                    .count();
+                crowd as f32 * weight
+            })
+            .collect();
+
+        for (ped_idx, cell) in positions {
+            let current_exit = self.exit_choice[&self.peds[ped_idx].id];
+            if let Some(best_exit) = self.nearest_exit(&cell, &crowding) {
+                if best_exit != current_exit {
+                    self.route_to_exit(ped_idx, best_exit, &cell);
+                    self.count_replan(self.peds[ped_idx].id);
+                }
+            }
+        }
+    }
+
+    //Exit with the lowest distance from cell plus any congestion penalty
+    fn nearest_exit(&self, cell: &Int2D, penalties: &[f32]) -> Option<usize> {
+        self.exit_distances
+            .iter()
+            .enumerate()
+            .filter(|(_, distances)| distance_at(distances, cell) != UNREACHABLE)
+            .map(|(exit_idx, distances)| {
+                let penalty = penalties.get(exit_idx).copied().unwrap_or(0.);
+                (exit_idx, distance_at(distances, cell) as f32 + penalty)
+            })
+            .min_by(|(_, a), (_, b)| a.total_cmp(b))
+            .map(|(exit_idx, _)| exit_idx)
+    }
+
+    fn route_to_exit(&mut self, ped_idx: usize, exit_idx: usize, cell: &Int2D) {
+        let forbids =
+            |from: &Int2D, to: &Int2D| self.doors.iter().any(|door| door.forbids_move(from, to));
+        let Some((path, exit_cell)) = descend(&self.exit_distances[exit_idx], cell, &forbids)
+        else {
+            return;
+        };
+        let id = self.peds[ped_idx].id;
+        let dest = Real2D {
+            x: exit_cell.x as f32,
+            y: exit_cell.y as f32,
+        };
+        let real_path: Vec<Real2D> = path
+            .into_iter()
+            .map(|node| Real2D {
+                x: node.x as f32,
+                y: node.y as f32,
+            })
+            .collect();
+
+        self.peds[ped_idx].dest = Some(dest);
+        self.pending_dests.insert(id, dest);
+        self.ped_paths.insert(id, real_path.into_iter());
+        self.exit_choice.insert(id, exit_idx);
+    }
+
+    pub fn record_evacuation(&mut self, id: u32) {
+        if let Some(exit_idx) = self.exit_choice.get(&id) {
+            self.evacuated.push((id, *exit_idx, self.step));
+        }
+    }
+
+    pub fn record_evacuation_curve(&mut self) {
+        if self.evacuation.is_some() {
+            let remaining = self.peds.len() - self.evacuated.len();
+            self.evacuation_curve.push((self.step, remaining));
+        }
+    }
+
+    /// Whether everybody is out, including those who had no way to an exit
+    pub fn evacuation_complete(&self) -> bool {
+        self.evacuation.is_some() && self.evacuated.len() == self.peds.len()
+    }
+
+    pub fn report_evacuation(&self) {
+        let Some(evacuation) = &self.evacuation else {
+            return;
+        };
+
+        match self.evacuated.iter().map(|(_, _, step)| step).max() {
+            Some(last_step) if self.evacuation_complete() => println!(
+                "Evacuation of {} pedestrians completed in {:.1} s",
+                self.evacuated.len(),
+                (last_step + 1) as f32 * self.step_duration
+            ),
+            _ => println!(
+                "{} of {} pedestrians evacuated after {:.1} s, {} left inside with no way to an exit",
+                self.evacuated.len(),
+                self.peds.len(),
+                self.step as f32 * self.step_duration,
+                self.stranded_peds.len()
+            ),
+        }
+        for (exit_idx, exit) in evacuation.exits.iter().enumerate() {
+            let usage = self
+                .evacuated
+                .iter()
+                .filter(|(_, used_exit, _)| *used_exit == exit_idx)
+                .count();
+            println!("Exit {}: {} pedestrians", exit.name, usage);
+        }
+
+        let rows = self.evacuation_curve.iter().map(|(step, remaining)| {
+            format!("{},{}", *step as f32 * self.step_duration, remaining)
+        });
+        if let Err(e) = output_path(
+            &self.output_dir,
+            &format!("evacuation_curve_{}.csv", self.run),
+        )
+        .and_then(|path| write_csv(&path, "time,remaining", rows))
+        {
+            println!("Failed to write evacuation curve: {}", e);
+        }
+    }
+}
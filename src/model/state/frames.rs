@@ -0,0 +1,99 @@
+use crate::model::{pedestrian::PedStatus, scenario::FrameOutput, state::state::ModelState};
+use crate::system_interface::{frame_writer::FrameWriter, raster_writer::heatmap_image};
+
+use image::imageops::{self, FilterType};
+use image::{Rgb, RgbImage};
+
+const FREE_CELL: Rgb<u8> = Rgb([255, 255, 255]);
+const OBSTACLE: Rgb<u8> = Rgb([0, 0, 0]);
+const WALKING: Rgb<u8> = Rgb([31, 119, 180]);
+const WAITING: Rgb<u8> = Rgb([148, 103, 189]);
+const DWELLING: Rgb<u8> = Rgb([44, 160, 44]);
+
+impl ModelState {
+    /// Draw the obstacle raster, the optional density heatmap and every pedestrian on the field
+    pub fn render_frame(&self, output: &FrameOutput) -> RgbImage {
+        let navigable = self.raster_navigable();
+        let background = if output.heatmap {
+            heatmap_image(&self.current_density(), output.max_density, &navigable)
+        } else {
+            let (height, width) = navigable.dim();
+            RgbImage::from_fn(width as u32, height as u32, |col, row| {
+                match navigable[[row as usize, col as usize]] {
+                    0 => OBSTACLE,
+                    _ => FREE_CELL,
+                }
+            })
+        };
+
+        let scale = output.scale.max(1);
+        let mut frame = imageops::resize(
+            &background,
+            background.width() * scale,
+            background.height() * scale,
+            FilterType::Nearest,
+        );
+
+        //Pedestrians are discs filling their cell
+        let radius = scale as f32 / 2.;
+        for ped in self.active_peds.values() {
+            let colour = match ped.status {
+                PedStatus::Waiting => WAITING,
+                PedStatus::Dwelling => DWELLING,
+                _ => WALKING,
+            };
+            let (centre_x, centre_y) = (
+                (ped.loc.x + 0.5) * scale as f32,
+                (ped.loc.y + 0.5) * scale as f32,
+            );
+            let (col, row) = (ped.loc.x as u32 * scale, ped.loc.y as u32 * scale);
+            for y in row..(row + scale).min(frame.height()) {
+                for x in col..(col + scale).min(frame.width()) {
+                    let (dx, dy) = (x as f32 + 0.5 - centre_x, y as f32 + 0.5 - centre_y);
+                    if dx * dx + dy * dy <= radius * radius {
+                        frame.put_pixel(x, y, colour);
+                    }
+                }
+            }
+        }
+        frame
+    }
+
+    /// Render and write a frame if the current step is one of the chosen ones
+    pub fn record_frame(&mut self) {
+        let Some(output) = &self.frame_output else {
+            return;
+        };
+        if !output.renders(self.step) {
+            return;
+        }
+
+        if self.frame_writer.is_none() {
+            match FrameWriter::new(
+                &self.output_dir,
+                &format!("frames_{}", self.run),
+                output.png,
+                output.gif,
+                output.frame_delay_ms,
+            ) {
+                Ok(writer) => self.frame_writer = Some(writer),
+                Err(e) => {
+                    println!("Failed to open frame output: {}", e);
+                    self.frame_output = None;
+                    return;
+                }
+            }
+        }
+
+        let frame = self.render_frame(output);
+        if let Some(writer) = self.frame_writer.as_mut() {
+            if let Err(e) = writer.write(self.step, frame) {
+                println!("Failed to write frame: {}", e);
+            }
+        }
+    }
+
+    pub fn finish_frames(&mut self) {
+        self.frame_writer = None;
+    }
+}
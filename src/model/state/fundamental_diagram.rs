@@ -0,0 +1,237 @@
+use std::collections::HashSet;
+
+use crate::model::{
+    calc_utils::density::{voronoi_cell_counts, voronoi_owners},
+    fundamental_diagram::{weidmann_speed, AreaVisit, FdMethod, FdSample},
+    pedestrian::{PedStatus, Pedestrian},
+    state::state::ModelState,
+};
+use crate::system_interface::{
+    output_writer::{output_path, write_csv},
+    plot_writer::{scatter_plot, side_by_side, PlotAxes, Series, SERIES_COLOURS},
+};
+
+use krabmaga::engine::location::{Int2D, Real2D};
+
+const FD_METHODS: [FdMethod; 3] = [FdMethod::B, FdMethod::C, FdMethod::D];
+
+impl ModelState {
+    //Speed in m/s of a pedestrian over the step just taken
+    fn step_speed(&self, ped: &Pedestrian) -> f32 {
+        (ped.last_d.x.powi(2) + ped.last_d.y.powi(2)).sqrt() * self.cell_size / self.step_duration
+    }
+
+    /// Measure density and speed in every measurement area after the step just taken
+    pub fn record_fundamental_diagram(&mut self) {
+        let Some(output) = &self.fd_output else {
+            return;
+        };
+        let walking: Vec<&Pedestrian> = self
+            .active_peds
+            .values()
+            .filter(|ped| ped.status != PedStatus::Arrived)
+            .collect();
+        let speeds: Vec<f32> = walking.iter().map(|ped| self.step_speed(ped)).collect();
+        let cell_area = self.cell_size * self.cell_size;
+
+        //Voronoi cells are shared by all areas, so they are computed once per step
+        let voronoi = output.methods.contains(&FdMethod::D).then(|| {
+            let positions: Vec<Real2D> = walking.iter().map(|ped| ped.loc).collect();
+            let owners = voronoi_owners(
+                &positions,
+                self.grid_dim(),
+                self.cell_size,
+                output.voronoi_radius,
+            );
+            let cell_counts = voronoi_cell_counts(&owners, positions.len());
+            (owners, cell_counts)
+        });
+
+        let mut samples = Vec::<FdSample>::new();
+        let mut visiting = HashSet::<(usize, u32)>::new();
+        for (area_idx, area) in output.areas.iter().enumerate() {
+            let cells = self.raster_free_cells(&area.region);
+            if cells.is_empty() {
+                continue;
+            }
+            let area_size = cells.len() as f32 * cell_area;
+            let inside: Vec<usize> = (0..walking.len())
+                .filter(|ped_idx| {
+                    let loc = walking[*ped_idx].loc;
+                    area.region.contains(&Int2D {
+                        x: loc.x as i32,
+                        y: loc.y as i32,
+                    })
+                })
+                .collect();
+            let count_density = inside.len() as f32 / area_size;
+
+            if output.methods.contains(&FdMethod::C) && !inside.is_empty() {
+                samples.push(FdSample {
+                    area: area_idx,
+                    method: FdMethod::C,
+                    step: self.step,
+                    id: None,
+                    density: count_density,
+                    speed: inside.iter().map(|ped_idx| speeds[*ped_idx]).sum::<f32>()
+                        / inside.len() as f32,
+                });
+            }
+
+            if let Some((owners, cell_counts)) = &voronoi {
+                //Every cell counts towards the density, but only cells within some
+                //pedestrian's Voronoi cell carry a speed
+                let (mut density_sum, mut speed_sum, mut owned) = (0., 0., 0);
+                for cell in cells.iter() {
+                    if let Some(owner) = owners[[cell.y as usize, cell.x as usize]] {
+                        density_sum += 1. / (cell_counts[owner] as f32 * cell_area);
+                        speed_sum += speeds[owner];
+                        owned += 1;
+                    }
+                }
+                if owned > 0 {
+                    samples.push(FdSample {
+                        area: area_idx,
+                        method: FdMethod::D,
+                        step: self.step,
+                        id: None,
+                        density: density_sum / cells.len() as f32,
+                        speed: speed_sum / owned as f32,
+                    });
+                }
+            }
+
+            if output.methods.contains(&FdMethod::B) {
+                //The move onto the first cell inside the area counts towards the visit
+                for ped_idx in inside {
+                    let ped = walking[ped_idx];
+                    let visit = self.fd_visits.entry((area_idx, ped.id)).or_default();
+                    visit.steps += 1;
+                    visit.distance += speeds[ped_idx] * self.step_duration;
+                    visit.density_sum += count_density;
+                    visiting.insert((area_idx, ped.id));
+                }
+            }
+        }
+
+        let left: Vec<(usize, u32)> = self
+            .fd_visits
+            .keys()
+            .filter(|key| !visiting.contains(*key))
+            .copied()
+            .collect();
+        for key in left {
+            self.close_visit(key);
+        }
+        self.fd_samples.extend(samples);
+    }
+
+    //Turn a finished method B visit into a sample
+    fn close_visit(&mut self, key: (usize, u32)) {
+        let Some(visit) = self.fd_visits.remove(&key) else {
+            return;
+        };
+        if visit.steps == 0 {
+            return;
+        }
+        let (area, id) = key;
+        let AreaVisit {
+            steps,
+            distance,
+            density_sum,
+        } = visit;
+        self.fd_samples.push(FdSample {
+            area,
+            method: FdMethod::B,
+            step: self.step,
+            id: Some(id),
+            density: density_sum / steps as f32,
+            speed: distance / (steps as f32 * self.step_duration),
+        });
+    }
+
+    /// Write every sample, and plot speed and flow against density for each area
+    pub fn write_fundamental_diagram(&mut self) {
+        if self.fd_output.is_none() {
+            return;
+        }
+        //Pedestrians still inside an area at the end of the run close their visit here
+        let open: Vec<(usize, u32)> = self.fd_visits.keys().copied().collect();
+        for key in open {
+            self.close_visit(key);
+        }
+        let Some(output) = &self.fd_output else {
+            return;
+        };
+
+        let rows = self.fd_samples.iter().map(|sample| {
+            format!(
+                "{},{:?},{},{},{},{},{},{}",
+                output.areas[sample.area].name,
+                sample.method,
+                sample.step,
+                sample.step as f32 * self.step_duration,
+                sample.id.map(|id| id.to_string()).unwrap_or_default(),
+                sample.density,
+                sample.speed,
+                sample.flow()
+            )
+        });
+        if let Err(e) = output_path(&self.output_dir, &format!("fd_{}.csv", self.run))
+            .and_then(|path| write_csv(&path, "area,method,step,time,id,density,speed,flow", rows))
+        {
+            println!("Failed to write fundamental diagram: {}", e);
+        }
+
+        if !output.png {
+            return;
+        }
+        let axes = PlotAxes {
+            x_max: output.max_density,
+            y_max: 2.,
+            x_tick: 1.,
+            y_tick: 0.5,
+        };
+        for (area_idx, area) in output.areas.iter().enumerate() {
+            let series = |value: fn(&FdSample) -> f32| -> Vec<Series> {
+                FD_METHODS
+                    .iter()
+                    .zip(SERIES_COLOURS)
+                    .map(|(method, colour)| Series {
+                        points: self
+                            .fd_samples
+                            .iter()
+                            .filter(|sample| sample.area == area_idx && sample.method == *method)
+                            .map(|sample| (sample.density, value(sample)))
+                            .collect(),
+                        colour,
+                    })
+                    .collect()
+            };
+            let speed_plot =
+                scatter_plot(&axes, &series(|sample| sample.speed), Some(&weidmann_speed));
+            let flow_plot = scatter_plot(
+                &axes,
+                &series(|sample| sample.flow()),
+                Some(&|density: f32| density * weidmann_speed(density)),
+            );
+            if let Err(e) = output_path(
+                &self.output_dir,
+                &format!("fd_{}_{}.png", self.run, area.name),
+            )
+            .map_err(anyhow::Error::from)
+            .and_then(|path| {
+                side_by_side(&[speed_plot, flow_plot])
+                    .save(path)
+                    .map_err(anyhow::Error::from)
+            }) {
+                println!("Failed to plot fundamental diagram: {}", e);
+            }
+        }
+    }
+
+    pub fn reset_fundamental_diagram(&mut self) {
+        self.fd_samples.clear();
+        self.fd_visits.clear();
+    }
+}
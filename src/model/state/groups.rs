@@ -0,0 +1,191 @@
+use crate::model::{
+    group::heading,
+    pedestrian::{PedStatus, Pedestrian},
+    state::{components::make_groups, state::ModelState},
+};
+
+use krabmaga::engine::location::{Int2D, Real2D};
+
+fn to_cell(loc: Real2D) -> Int2D {
+    Int2D {
+        x: loc.x as i32,
+        y: loc.y as i32,
+    }
+}
+
+//Number of king moves between two cells
+fn cell_gap(a: Real2D, b: Real2D) -> i32 {
+    let (a, b) = (to_cell(a), to_cell(b));
+    (a.x - b.x).abs().max((a.y - b.y).abs())
+}
+
+impl ModelState {
+    /// Draw the groups of the current population, moving members next to their leaders
+    pub fn form_groups(&mut self) {
+        self.groups = match &self.group_demand {
+            Some(demand) => make_groups(&mut self.peds, demand, &self.obj_grid, &mut self.rng),
+            None => Vec::new(),
+        };
+        self.index_groups();
+    }
+
+    pub fn index_groups(&mut self) {
+        self.group_of.clear();
+        for (group_idx, group) in self.groups.iter().enumerate() {
+            if group.members.is_empty() {
+                continue;
+            }
+            self.group_of.insert(group.leader, group_idx);
+            for member in group.members.iter() {
+                self.group_of.insert(*member, group_idx);
+            }
+        }
+    }
+
+    fn max_gap(&self) -> i32 {
+        self.group_demand
+            .as_ref()
+            .map_or(0, |demand| demand.max_gap)
+    }
+
+    /// Whether a group leader at `loc` has to wait for a member that fell behind
+    pub fn waits_for_group(&self, id: u32, loc: Real2D) -> bool {
+        let Some(group) = self.group_of.get(&id).map(|idx| &self.groups[*idx]) else {
+            return false;
+        };
+        group.leader == id
+            && group.members.iter().any(|member| {
+                self.active_peds.get(member).is_some_and(|ped| {
+                    ped.status != PedStatus::Arrived && cell_gap(ped.loc, loc) > self.max_gap()
+                })
+            })
+    }
+
+    /// Next position of a member walking in formation, and whether it is standing still, or
+    /// None if the pedestrian walks on its own. Members take the free neighbouring cell closest
+    /// to their place in the formation, and catch up along the shortest path when too far behind.
+    pub fn formation_move(&mut self, ped: &Pedestrian) -> Option<(Real2D, bool)> {
+        let group = &self.groups[*self.group_of.get(&ped.id)?];
+        if group.leader == ped.id {
+            return None;
+        }
+        let leader = self
+            .active_peds
+            .get(&group.leader)
+            .filter(|leader| leader.status != PedStatus::Arrived)?;
+        let facing = heading(leader.last_d)
+            .or_else(|| {
+                heading(Real2D {
+                    x: leader.dir_x,
+                    y: leader.dir_y,
+                })
+            })
+            .unwrap_or((1., 0.));
+        let slot = group.slot(ped.id, leader.loc, facing)?;
+        let leader_loc = leader.loc;
+
+        let next = if cell_gap(ped.loc, leader_loc) > self.max_gap() {
+            self.catch_up_move(ped, leader_loc)
+        } else {
+            self.catch_up_paths.remove(&ped.id);
+            let here = to_cell(ped.loc);
+            let distance = |cell: &Int2D| (cell.x as f32 - slot.x).hypot(cell.y as f32 - slot.y);
+            //Members only move when it brings them closer to their place, one of the four
+            //moves paths are made of
+            let best = [(-1, 0), (1, 0), (0, -1), (0, 1)]
+                .iter()
+                .map(|(dx, dy)| Int2D {
+                    x: here.x + dx,
+                    y: here.y + dy,
+                })
+                .filter(|cell| {
+                    cell.x >= 0
+                        && cell.y >= 0
+                        && cell.x < self.obj_grid.width
+                        && cell.y < self.obj_grid.height
+                        && self.obj_grid.get_value(cell).is_none()
+                        && !self.doors.iter().any(|door| door.forbids_move(&here, cell))
+                })
+                .fold(here, |best, cell| match distance(&cell) < distance(&best) {
+                    true => cell,
+                    false => best,
+                });
+            Real2D {
+                x: best.x as f32,
+                y: best.y as f32,
+            }
+        };
+
+        if to_cell(next) == to_cell(ped.loc) || !self.may_advance(ped.id, ped.loc, next) {
+            return Some((ped.loc, true));
+        }
+        if let Some((_, path)) = self.catch_up_paths.get_mut(&ped.id) {
+            path.next();
+        }
+        Some((next, false))
+    }
+
+    //Next point on the way of a member that fell behind towards its leader. The path is kept
+    //until the leader moves to another cell, rather than planned again every step.
+    fn catch_up_move(&mut self, ped: &Pedestrian, leader_loc: Real2D) -> Real2D {
+        let leader_cell = to_cell(leader_loc);
+        let planned = self
+            .catch_up_paths
+            .get(&ped.id)
+            .is_some_and(|(cell, path)| *cell == leader_cell && !path.as_slice().is_empty());
+        if !planned {
+            //Paths hold the origin, which the member is standing on
+            let path: Vec<Real2D> = self
+                .cheapest_route(ped, ped.loc, leader_loc)
+                .map(|path| path.into_iter().skip(1).collect())
+                .unwrap_or_default();
+            self.catch_up_paths
+                .insert(ped.id, (leader_cell, path.into_iter()));
+        }
+        self.catch_up_paths[&ped.id]
+            .1
+            .as_slice()
+            .first()
+            .copied()
+            .unwrap_or(leader_loc)
+    }
+
+    /// Let the members of an arriving leader finish the trip on their own, towards the
+    /// leader's destination. A member arriving just leaves its group.
+    pub fn release_group(&mut self, ped: &Pedestrian) {
+        let Some(group_idx) = self.group_of.remove(&ped.id) else {
+            return;
+        };
+        let group = &mut self.groups[group_idx];
+        if group.leader != ped.id {
+            self.catch_up_paths.remove(&ped.id);
+            if let Some(idx) = group.members.iter().position(|member| *member == ped.id) {
+                group.members.remove(idx);
+                group.offsets.remove(idx);
+            }
+            return;
+        }
+
+        let members = std::mem::take(&mut self.groups[group_idx].members);
+        self.groups[group_idx].offsets.clear();
+        let exit = self.exit_choice.get(&ped.id).copied();
+        for member in members {
+            self.group_of.remove(&member);
+            self.catch_up_paths.remove(&member);
+            let (Some(member_ped), Some(dest)) = (self.active_peds.get(&member).copied(), ped.dest)
+            else {
+                continue;
+            };
+            self.pending_dests.insert(member, dest);
+            if let Some(exit) = exit {
+                self.exit_choice.insert(member, exit);
+            }
+            match self.plan_route(&member_ped, member_ped.loc, dest) {
+                Ok(path) => {
+                    self.ped_paths.insert(member, path.into_iter());
+                }
+                Err(e) => println!("Group member {} cannot go on alone: {}", member, e),
+            }
+        }
+    }
+}
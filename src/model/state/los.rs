@@ -0,0 +1,129 @@
+use crate::model::{los::LosClass, scenario::Region, state::state::ModelState};
+use crate::system_interface::output_writer::{output_path, write_csv};
+
+use image::{Rgb, RgbImage};
+use itertools::iproduct;
+use ndarray::Array2;
+
+impl ModelState {
+    //Mean density over the free cells of a region
+    fn region_density(&self, density: &Array2<f32>, region: &Region) -> f32 {
+        let values: Vec<f32> = self
+            .raster_free_cells(region)
+            .iter()
+            .map(|cell| density[[cell.y as usize, cell.x as usize]])
+            .collect();
+        if values.is_empty() {
+            return 0.;
+        }
+        values.iter().sum::<f32>() / values.len() as f32
+    }
+
+    /// Classify every zone by its current density, and add the density to the per-cell LOS map
+    pub fn record_los(&mut self) {
+        let Some(output) = self.los_output.clone() else {
+            return;
+        };
+        if !self.step.is_multiple_of(output.interval.max(1)) {
+            return;
+        }
+
+        let density = self.step_density();
+        let zone_classes: Vec<LosClass> = output
+            .zones
+            .iter()
+            .map(|zone| LosClass::from_density(self.region_density(&density, &zone.region)))
+            .collect();
+
+        for (counts, class) in self.zone_los_counts.iter_mut().zip(zone_classes) {
+            counts[class.index()] += 1;
+        }
+        for (idx, cell_density) in density.indexed_iter() {
+            self.los_cell_counts[LosClass::from_density(*cell_density).index()][idx] += 1;
+        }
+        self.los_density_sum += &density;
+        self.los_samples += 1;
+    }
+
+    /// Write the fraction of time each zone and each cell spent in each class, and a map of
+    /// the LOS of the mean density in every cell
+    pub fn write_los(&self) {
+        let Some(output) = &self.los_output else {
+            return;
+        };
+        if self.los_samples == 0 {
+            return;
+        }
+
+        let header = format!(
+            "zone,{}",
+            LosClass::ALL
+                .iter()
+                .map(|class| class.to_string())
+                .collect::<Vec<String>>()
+                .join(",")
+        );
+        let rows = output
+            .zones
+            .iter()
+            .zip(self.zone_los_counts.iter())
+            .map(|(zone, counts)| {
+                let fractions: Vec<String> = counts
+                    .iter()
+                    .map(|count| format!("{:.4}", *count as f32 / self.los_samples as f32))
+                    .collect();
+                format!("{},{}", zone.name, fractions.join(","))
+            });
+        if let Err(e) = output_path(&self.output_dir, &format!("los_{}_zones.csv", self.run))
+            .and_then(|path| write_csv(&path, &header, rows))
+        {
+            println!("Failed to write LOS table: {}", e);
+        }
+
+        let navigable = self.raster_navigable();
+        let (height, width) = navigable.dim();
+        let rows = iproduct!(0..height, 0..width)
+            .filter(|idx| navigable[*idx] != 0)
+            .map(|(row, col)| {
+                let fractions: Vec<String> = self
+                    .los_cell_counts
+                    .iter()
+                    .map(|counts| {
+                        format!("{:.4}", counts[[row, col]] as f32 / self.los_samples as f32)
+                    })
+                    .collect();
+                format!("{},{},{}", col, row, fractions.join(","))
+            });
+        if let Err(e) = output_path(&self.output_dir, &format!("los_{}_cells.csv", self.run))
+            .and_then(|path| write_csv(&path, &header.replacen("zone", "x,y", 1), rows))
+        {
+            println!("Failed to write LOS cell table: {}", e);
+        }
+
+        let mean_density = &self.los_density_sum / self.los_samples as f32;
+        let map = RgbImage::from_fn(width as u32, height as u32, |col, row| {
+            let idx = [row as usize, col as usize];
+            match navigable[idx] {
+                0 => Rgb([0, 0, 0]),
+                _ => LosClass::from_density(mean_density[idx]).colour(),
+            }
+        });
+        if let Err(e) = output_path(&self.output_dir, &format!("los_{}_map.png", self.run))
+            .map_err(anyhow::Error::from)
+            .and_then(|path| map.save(path).map_err(anyhow::Error::from))
+        {
+            println!("Failed to write LOS map: {}", e);
+        }
+    }
+
+    pub fn reset_los(&mut self) {
+        let zones = self
+            .los_output
+            .as_ref()
+            .map_or(0, |output| output.zones.len());
+        self.zone_los_counts = vec![[0; 6]; zones];
+        self.los_cell_counts = vec![Array2::zeros(self.grid_dim()); 6];
+        self.los_density_sum = Array2::zeros(self.grid_dim());
+        self.los_samples = 0;
+    }
+}
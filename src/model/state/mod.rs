@@ -1,2 +1,22 @@
+pub mod activities;
 pub mod components;
+pub mod congestion;
+pub mod counting_lines;
+pub mod crossings;
+pub mod density;
+pub mod doors;
+pub mod dynamic_obstacles;
+pub mod evacuation;
+pub mod frames;
+pub mod fundamental_diagram;
+pub mod groups;
+pub mod los;
+pub mod population;
+pub mod profiles;
+pub mod route_choice;
+pub mod services;
+pub mod snapshots;
 pub mod state;
+pub mod streaming;
+pub mod trajectories;
+pub mod trips;
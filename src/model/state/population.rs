@@ -0,0 +1,78 @@
+use std::collections::HashMap;
+
+use crate::model::{
+    calc_utils::distance_field::wall_distance_field,
+    poi::DestinationSampler,
+    scenario::PopulationMode,
+    state::{
+        components::{assign_profiles, make_paths, make_peds},
+        state::ModelState,
+    },
+};
+
+impl ModelState {
+    /// Plan the paths of the current population, or route it to the exits in evacuation mode
+    pub fn plan_population_paths(&mut self) {
+        self.exit_choice.clear();
+        self.wall_distances = wall_distance_field(&self.obj_grid);
+        let ped_paths = match self.evacuation {
+            Some(_) => HashMap::new(),
+            None => make_paths(&self.peds, &|ped, origin, dest| {
+                self.plan_route(ped, origin, dest)
+            }),
+        };
+        self.ped_paths = ped_paths;
+        self.assign_nearest_exits();
+    }
+
+    /// Put back the initial population, or draw a new one, with fresh paths for the next run.
+    /// In replay mode the random number generator is rewound too, so the run repeats exactly.
+    pub fn reset_population(&mut self) {
+        //Nothing to do if the current population has not been scheduled yet
+        if !self.population_used {
+            return;
+        }
+        //Puts the grid back to the bare raster, which resampling and replanning below rely on
+        self.reopen_closures();
+        self.pending_dests.clear();
+        self.stranded_peds.clear();
+        self.dwelling.clear();
+
+        match self.population_mode {
+            PopulationMode::Replay => {
+                self.peds = self.initial_peds.clone();
+                self.groups = self.initial_groups.clone();
+                self.itineraries = self.initial_itineraries.clone();
+                self.rng = self.initial_rng.clone();
+                self.index_groups();
+            }
+            PopulationMode::Resample => {
+                let destinations = DestinationSampler::new(
+                    &self.pois,
+                    self.destination_choice.as_ref(),
+                    &self.obj_grid,
+                    self.cell_size,
+                );
+                self.peds = make_peds(
+                    self.num_agents,
+                    self.dim,
+                    &self.obj_grid,
+                    destinations.as_ref(),
+                    &mut self.rng,
+                );
+                assign_profiles(
+                    &mut self.peds,
+                    &self.profiles,
+                    self.cell_size,
+                    self.step_duration,
+                    &mut self.rng,
+                );
+                self.draw_route_seed();
+                self.draw_itineraries();
+                self.form_groups();
+            }
+        }
+        self.plan_population_paths();
+        self.population_used = false;
+    }
+}
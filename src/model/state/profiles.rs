@@ -0,0 +1,59 @@
+use crate::model::{
+    pedestrian::Pedestrian,
+    profile::RouteCosts,
+    state::{components::plan_weighted_path, state::ModelState},
+    trip::{TripRecord, TripSummary},
+};
+
+use krabmaga::engine::location::Real2D;
+use std::collections::BTreeMap;
+
+impl ModelState {
+    pub fn route_costs(&self) -> RouteCosts<'_> {
+        RouteCosts {
+            profiles: &self.profiles,
+            wall_distances: &self.wall_distances,
+            stairs: &self.stairs,
+            zones: &self.route_zones,
+            cell_size: self.cell_size,
+        }
+    }
+
+    /// Cheapest path from origin to dest for this pedestrian's profile
+    pub fn cheapest_route(
+        &self,
+        ped: &Pedestrian,
+        origin: Real2D,
+        dest: Real2D,
+    ) -> Result<Vec<Real2D>, anyhow::Error> {
+        let costs = self.route_costs();
+        plan_weighted_path(origin, dest, &self.obj_grid, &self.doors, &|cell| {
+            costs.cell_cost(ped, cell)
+        })
+    }
+
+    pub fn profile_name(&self, ped: &Pedestrian) -> String {
+        self.profiles
+            .get(ped.profile)
+            .map(|profile| profile.name.clone())
+            .unwrap_or_default()
+    }
+
+    /// Trip summary of every profile, by profile name
+    pub fn profile_summaries(&self, records: &[TripRecord]) -> BTreeMap<String, TripSummary> {
+        self.profiles
+            .iter()
+            .map(|profile| {
+                let profile_records: Vec<TripRecord> = records
+                    .iter()
+                    .filter(|record| record.profile == profile.name)
+                    .cloned()
+                    .collect();
+                (
+                    profile.name.clone(),
+                    TripSummary::from_records(self.run, &profile_records),
+                )
+            })
+            .collect()
+    }
+}
@@ -0,0 +1,119 @@
+use std::collections::HashSet;
+
+use crate::model::{
+    pedestrian::Pedestrian,
+    route_choice::{keyed_uniform, RouteChoiceModel},
+    state::{components::plan_weighted_path, state::ModelState},
+};
+
+use krabmaga::engine::location::{Int2D, Real2D};
+use krabmaga::rand::Rng;
+
+fn to_cell(point: &Real2D) -> (i32, i32) {
+    (point.x as i32, point.y as i32)
+}
+
+impl ModelState {
+    /// Draw the seed of the route choices of a new population
+    pub fn draw_route_seed(&mut self) {
+        if self.route_choice.is_some() {
+            self.route_seed = self.rng.gen();
+        }
+    }
+
+    /// Path from origin to dest for this pedestrian, by its profile's route costs and the
+    /// scenario's route choice model. Without one, everybody takes the cheapest route.
+    pub fn plan_route(
+        &self,
+        ped: &Pedestrian,
+        origin: Real2D,
+        dest: Real2D,
+    ) -> Result<Vec<Real2D>, anyhow::Error> {
+        self.plan_route_weighted(ped, origin, dest, &|_| 1.)
+    }
+
+    /// Like `plan_route`, with the route cost of every cell multiplied by `factor`, which must
+    /// be 1 or more, e.g. to steer around crowds
+    pub fn plan_route_weighted(
+        &self,
+        ped: &Pedestrian,
+        origin: Real2D,
+        dest: Real2D,
+        factor: &dyn Fn(&Int2D) -> f32,
+    ) -> Result<Vec<Real2D>, anyhow::Error> {
+        let route_costs = self.route_costs();
+        let cell_cost = |cell: &Int2D| {
+            route_costs
+                .cell_cost(ped, cell)
+                .map(|cost| cost * factor(cell))
+        };
+        let Some(choice) = &self.route_choice else {
+            return plan_weighted_path(origin, dest, &self.obj_grid, &self.doors, &cell_cost);
+        };
+
+        match choice.model {
+            RouteChoiceModel::Perturbation => {
+                plan_weighted_path(origin, dest, &self.obj_grid, &self.doors, &|cell| {
+                    cell_cost(cell)
+                        .map(|cost| cost * choice.perturbation(self.route_seed, ped.id, cell))
+                })
+            }
+            RouteChoiceModel::Logit => {
+                let (origin_x, origin_y) = to_cell(&origin);
+                let (dest_x, dest_y) = to_cell(&dest);
+                let mut candidates = Vec::<(Vec<Real2D>, f32)>::new();
+                //Cells on the candidates found so far
+                let mut used = HashSet::<(i32, i32)>::new();
+                for _ in 0..choice.k.max(1) {
+                    let path = match plan_weighted_path(
+                        origin,
+                        dest,
+                        &self.obj_grid,
+                        &self.doors,
+                        &|cell| {
+                            cell_cost(cell).map(|cost| match used.contains(&(cell.x, cell.y)) {
+                                true => cost * choice.overlap_penalty.max(1.),
+                                false => cost,
+                            })
+                        },
+                    ) {
+                        Ok(path) => path,
+                        Err(e) if candidates.is_empty() => return Err(e),
+                        Err(_) => break,
+                    };
+                    let cells: Vec<(i32, i32)> = path.iter().map(to_cell).collect();
+                    used.extend(cells.iter().copied());
+                    if candidates.iter().any(|(candidate, _)| {
+                        candidate.iter().map(to_cell).eq(cells.iter().copied())
+                    }) {
+                        continue;
+                    }
+
+                    //Unpenalised cost of every cell entered, the origin excluded and the
+                    //destination, which paths leave out, included
+                    let cost: f32 = cells
+                        .iter()
+                        .skip(1)
+                        .chain(std::iter::once(&(dest_x, dest_y)))
+                        .map(|(x, y)| cell_cost(&Int2D { x: *x, y: *y }).unwrap_or(1.))
+                        .sum();
+                    candidates.push((path, cost));
+                }
+
+                let draw = keyed_uniform(
+                    self.route_seed,
+                    &[
+                        ped.id as i64,
+                        origin_x as i64,
+                        origin_y as i64,
+                        dest_x as i64,
+                        dest_y as i64,
+                    ],
+                );
+                let route_costs: Vec<f32> = candidates.iter().map(|(_, cost)| *cost).collect();
+                let chosen = choice.choose(&route_costs, draw);
+                Ok(candidates.swap_remove(chosen).0)
+            }
+        }
+    }
+}
@@ -0,0 +1,242 @@
+use crate::model::{
+    pedestrian::Pedestrian,
+    service::{ServiceState, ServiceSummary},
+    state::{components::plan_weighted_path, state::ModelState},
+};
+use crate::system_interface::output_writer::{output_path, write_csv};
+
+use anyhow::anyhow;
+use krabmaga::engine::location::{Int2D, Real2D};
+
+fn to_cell(loc: Real2D) -> Int2D {
+    Int2D {
+        x: loc.x as i32,
+        y: loc.y as i32,
+    }
+}
+
+//Number of king moves between two cells
+fn cell_gap(a: Real2D, b: Real2D) -> i32 {
+    let (a, b) = (to_cell(a), to_cell(b));
+    (a.x - b.x).abs().max((a.y - b.y).abs())
+}
+
+impl ModelState {
+    pub fn reset_services(&mut self) {
+        self.service_states = vec![ServiceState::default(); self.services.len()];
+        self.service_passes.clear();
+        self.queue_lengths.clear();
+    }
+
+    /// Index of the service point a move from `loc` onto `next` steps onto.
+    /// Group members are served with their leader, so their moves enter none.
+    pub fn service_entered(&self, id: u32, loc: Real2D, next: Real2D) -> Option<usize> {
+        if let Some(group_idx) = self.group_of.get(&id) {
+            if self.groups[*group_idx].leader != id {
+                return None;
+            }
+        }
+        let (loc_cell, next_cell) = (to_cell(loc), to_cell(next));
+        self.services.iter().position(|service| {
+            service.region.contains(&next_cell) && !service.region.contains(&loc_cell)
+        })
+    }
+
+    /// Whether a pedestrian has been called to a service point
+    pub fn service_open(&self, id: u32) -> bool {
+        self.service_passes.contains_key(&id)
+    }
+
+    /// Put a pedestrian at the back of the queue of a service point, unless it is already in it
+    pub fn join_queue(&mut self, id: u32, idx: usize) {
+        let service_state = &mut self.service_states[idx];
+        if !service_state.queue.contains(&id) && !service_state.serving.contains(&id) {
+            service_state.queue.push_back(id);
+            service_state.joined.insert(id, self.step);
+        }
+    }
+
+    /// Use up a pedestrian's call, serving it where it steps onto the service point
+    pub fn pass_service(&mut self, id: u32) {
+        if let Some(service_steps) = self.service_passes.remove(&id) {
+            self.dwelling.insert(id, self.step + 1 + service_steps);
+        }
+    }
+
+    /// Next position of a queueing pedestrian moving up to its place in the queue, and whether
+    /// it is standing still, or None if the pedestrian is not queueing
+    pub fn queue_move(&mut self, ped: &Pedestrian) -> Option<(Real2D, bool)> {
+        let (idx, place) =
+            self.service_states
+                .iter()
+                .enumerate()
+                .find_map(|(idx, service_state)| {
+                    service_state
+                        .queue
+                        .iter()
+                        .position(|id| *id == ped.id)
+                        .map(|place| (idx, place))
+                })?;
+        let slot = self.services[idx].queue_slot(place);
+        if to_cell(ped.loc) == to_cell(slot) {
+            return Some((ped.loc, true));
+        }
+        //The way up the queue goes around the service point rather than through it
+        let region = self.services[idx].region;
+        let costs = self.route_costs();
+        let path =
+            plan_weighted_path(
+                ped.loc,
+                slot,
+                &self.obj_grid,
+                &self.doors,
+                &|cell| match region.contains(cell) {
+                    true => None,
+                    false => costs.cell_cost(ped, cell),
+                },
+            );
+        //Paths hold the origin but not the destination, so a neighbouring slot is the next move
+        let next = match path {
+            Ok(path) => path.get(1).copied().unwrap_or(slot),
+            Err(_) => return Some((ped.loc, true)),
+        };
+        //Moving up still waits for signals and doors
+        match self.may_advance(ped.id, ped.loc, next) {
+            true => Some((next, false)),
+            false => Some((ped.loc, true)),
+        }
+    }
+
+    //Path from where the pedestrian stands through `via` to its destination
+    fn route_through(&self, ped: &Pedestrian, via: Real2D) -> Result<Vec<Real2D>, anyhow::Error> {
+        let dest = ped
+            .dest
+            .ok_or_else(|| anyhow!("Pedestrian {} has no destination", ped.id))?;
+        let mut path = self.plan_route(ped, ped.loc, via)?;
+        path.extend(self.plan_route(ped, via, dest)?);
+        Ok(path)
+    }
+
+    /// Call the heads of the queues to every free server, and sample the queue lengths.
+    /// Called pedestrians walk from the front of the queue onto the service point.
+    pub fn serve_queues(&mut self) {
+        for idx in 0..self.services.len() {
+            //Servers stay busy until their pedestrian has used its call and been served
+            let step = self.step;
+            let (active_peds, passes, dwelling) =
+                (&self.active_peds, &self.service_passes, &self.dwelling);
+            let service_state = &mut self.service_states[idx];
+            service_state.serving.retain(|id| {
+                active_peds.contains_key(id)
+                    && (passes.contains_key(id)
+                        || dwelling.get(id).is_some_and(|until| step < *until))
+            });
+
+            let service = &self.services[idx];
+            //Free cell of the service point nearest to the head of the queue
+            let head_slot = service.queue_slot(0);
+            let service_cell = service
+                .region
+                .free_cells(&self.obj_grid)
+                .into_iter()
+                .min_by(|a, b| {
+                    let gap = |cell: &Real2D| (cell.x - head_slot.x).hypot(cell.y - head_slot.y);
+                    gap(a).total_cmp(&gap(b))
+                });
+
+            while self.service_states[idx].serving.len() < self.services[idx].servers {
+                let Some(head) = self.service_states[idx].queue.front().copied() else {
+                    break;
+                };
+                let Some(ped) = self.active_peds.get(&head).copied() else {
+                    self.service_states[idx].queue.pop_front();
+                    continue;
+                };
+                //The head is called once it has reached the front of the queue
+                if cell_gap(ped.loc, head_slot) > 1 {
+                    break;
+                }
+                self.service_states[idx].queue.pop_front();
+
+                let path = service_cell
+                    .ok_or_else(|| anyhow!("the service point has no free cell"))
+                    .and_then(|cell| self.route_through(&ped, cell));
+                let path = match path {
+                    Ok(path) => path,
+                    Err(e) => {
+                        println!(
+                            "Pedestrian {} cannot reach service point {}: {}",
+                            head, self.services[idx].name, e
+                        );
+                        self.service_states[idx].joined.remove(&head);
+                        continue;
+                    }
+                };
+                self.ped_paths.insert(head, path.into_iter());
+
+                let service_time = self.services[idx].service_time.sample(&mut self.rng);
+                let service_steps = (service_time.max(0.) / self.step_duration).round() as u64;
+                self.service_passes.insert(head, service_steps);
+
+                let service_state = &mut self.service_states[idx];
+                let joined = service_state.joined.remove(&head).unwrap_or(self.step);
+                service_state
+                    .waits
+                    .push((self.step - joined) as f32 * self.step_duration);
+                service_state.served += 1;
+                service_state.serving.push(head);
+            }
+
+            let service_state = &mut self.service_states[idx];
+            let queue_length = service_state.queue.len();
+            service_state.queue_length_sum += queue_length as u64;
+            service_state.samples += 1;
+            service_state.max_queue = service_state.max_queue.max(queue_length);
+            self.queue_lengths
+                .push((self.step, idx, queue_length, service_state.serving.len()));
+        }
+    }
+
+    /// Print the queue statistics of every service point and write them with the queue lengths
+    pub fn write_services(&self) {
+        if self.services.is_empty() {
+            return;
+        }
+        let summaries: Vec<ServiceSummary> = self
+            .services
+            .iter()
+            .zip(self.service_states.iter())
+            .map(|(service, service_state)| ServiceSummary::new(service, service_state))
+            .collect();
+        for summary in summaries.iter() {
+            println!(
+                "Service point {}: {} served, mean wait {:.1} s, longest queue {}, {} still queueing",
+                summary.name,
+                summary.served,
+                summary.mean_wait,
+                summary.max_queue_length,
+                summary.still_queueing
+            );
+        }
+        self.write_json(&format!("queues_{}_summary.json", self.run), &summaries);
+
+        let rows = self
+            .queue_lengths
+            .iter()
+            .map(|(step, idx, queue_length, serving)| {
+                format!(
+                    "{},{},{},{},{}",
+                    self.services[*idx].name,
+                    step,
+                    *step as f32 * self.step_duration,
+                    queue_length,
+                    serving
+                )
+            });
+        if let Err(e) = output_path(&self.output_dir, &format!("queues_{}.csv", self.run))
+            .and_then(|path| write_csv(&path, "service,step,time,queue_length,serving", rows))
+        {
+            println!("Failed to write queue lengths: {}", e);
+        }
+    }
+}
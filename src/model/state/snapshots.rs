@@ -0,0 +1,164 @@
+use crate::model::{
+    pedestrian::Pedestrian,
+    snapshot::{
+        from_cell_point, from_point, to_cell_point, to_point, PedestrianRecord, RngState, Snapshot,
+    },
+    state::state::ModelState,
+};
+use crate::system_interface::{output_writer::output_path, snapshot_io::write_snapshot};
+
+use anyhow::anyhow;
+use itertools::iproduct;
+use krabmaga::engine::{location::Int2D, schedule::Schedule};
+
+impl ModelState {
+    pub fn take_snapshot(&self) -> Snapshot {
+        let mut agents: Vec<PedestrianRecord> = self
+            .active_peds
+            .values()
+            .map(PedestrianRecord::from)
+            .collect();
+        agents.sort_by_key(|agent| agent.id);
+
+        Snapshot {
+            step: self.step,
+            dim: self.dim,
+            peds: self.peds.iter().map(PedestrianRecord::from).collect(),
+            agents,
+            paths: self
+                .ped_paths
+                .iter()
+                .map(|(id, path)| (*id, path.as_slice().iter().map(to_point).collect()))
+                .collect(),
+            obstacles: iproduct!(0..self.obj_grid.width, 0..self.obj_grid.height)
+                .map(|(x, y)| Int2D { x, y })
+                .filter(|cell| self.raster_obstacles.contains(cell))
+                .map(|cell| to_cell_point(&cell))
+                .collect(),
+            pending_dests: self
+                .pending_dests
+                .iter()
+                .map(|(id, dest)| (*id, to_point(dest)))
+                .collect(),
+            applied_closures: self
+                .applied_closures
+                .iter()
+                .map(|(idx, cells)| (*idx, cells.iter().map(to_cell_point).collect()))
+                .collect(),
+            stranded_peds: self.stranded_peds.iter().copied().collect(),
+            crossing_stats: self.crossing_stats.clone(),
+            crossing_waits: self.crossing_waits.clone(),
+            door_states: self.door_states.clone(),
+            exit_choice: self.exit_choice.clone(),
+            evacuated: self.evacuated.clone(),
+            trips: self.trips.values().cloned().collect(),
+            rng: RngState::capture(&self.rng),
+            groups: self.groups.clone(),
+            itineraries: self.itineraries.clone(),
+            dwelling: self.dwelling.clone(),
+            route_seed: self.route_seed,
+            service_states: self.service_states.clone(),
+            service_passes: self.service_passes.clone(),
+        }
+    }
+
+    /// Write a snapshot if the step just taken is one of the scenario's snapshot steps
+    pub fn record_snapshot(&self) {
+        if !self.snapshot_steps.contains(&self.step) {
+            return;
+        }
+        if let Err(e) = output_path(
+            &self.output_dir,
+            &format!("snapshot_{}_{}.json", self.run, self.step),
+        )
+        .map_err(anyhow::Error::from)
+        .and_then(|path| write_snapshot(&path, &self.take_snapshot()))
+        {
+            println!("Failed to write snapshot: {}", e);
+        }
+    }
+
+    /// Start every following run from a snapshot instead of the initial population.
+    /// The scenario may differ from the one the snapshot was taken with, but the grid size may not.
+    pub fn restore_snapshot(&mut self, snapshot: Snapshot) -> Result<(), anyhow::Error> {
+        if snapshot.dim != self.dim {
+            return Err(anyhow!(
+                "Snapshot grid is {:?}, but the obstacle grid is {:?}",
+                snapshot.dim,
+                self.dim
+            ));
+        }
+        if snapshot.crossing_stats.len() != self.crossings.len()
+            || snapshot.door_states.len() != self.doors.len()
+        {
+            return Err(anyhow!(
+                "Snapshot has {} crossings and {} doors, but the scenario has {} and {}",
+                snapshot.crossing_stats.len(),
+                snapshot.door_states.len(),
+                self.crossings.len(),
+                self.doors.len()
+            ));
+        }
+        self.restore_from = Some(snapshot);
+        Ok(())
+    }
+
+    //Put the state back as it was in the snapshot and schedule the agents still on the field
+    pub fn apply_snapshot(&mut self, snapshot: Snapshot, schedule: &mut Schedule) {
+        self.step = snapshot.step;
+        self.peds = snapshot.peds.iter().map(Pedestrian::from).collect();
+        self.active_peds = snapshot
+            .agents
+            .iter()
+            .map(|record| (record.id, Pedestrian::from(record)))
+            .collect();
+        self.ped_paths = snapshot
+            .paths
+            .iter()
+            .map(|(id, path)| {
+                let points: Vec<_> = path.iter().map(from_point).collect();
+                (*id, points.into_iter())
+            })
+            .collect();
+
+        self.pending_dests = snapshot
+            .pending_dests
+            .iter()
+            .map(|(id, dest)| (*id, from_point(dest)))
+            .collect();
+        self.applied_closures = snapshot
+            .applied_closures
+            .iter()
+            .map(|(idx, cells)| (*idx, cells.iter().map(from_cell_point).collect()))
+            .collect();
+        self.raster_obstacles = snapshot.obstacles.iter().map(from_cell_point).collect();
+        self.rebuild_obj_grid();
+        self.stranded_peds = snapshot.stranded_peds.into_iter().collect();
+        self.crossing_stats = snapshot.crossing_stats;
+        self.crossing_waits = snapshot.crossing_waits;
+        self.door_states = snapshot.door_states;
+        self.exit_choice = snapshot.exit_choice;
+        self.evacuated = snapshot.evacuated;
+        self.trips = snapshot
+            .trips
+            .into_iter()
+            .map(|trip| (trip.id, trip))
+            .collect();
+        self.rng = snapshot.rng.restore();
+        self.groups = snapshot.groups;
+        self.itineraries = snapshot.itineraries;
+        self.dwelling = snapshot.dwelling;
+        self.route_seed = snapshot.route_seed;
+        //Queues of snapshots taken without the scenario's service points start empty
+        if snapshot.service_states.len() == self.services.len() {
+            self.service_states = snapshot.service_states;
+            self.service_passes = snapshot.service_passes;
+        }
+        self.index_groups();
+
+        for agent in self.active_peds.values() {
+            schedule.schedule_repeating(Box::new(*agent), 0., 0);
+            self.field.set_object_location(*agent, agent.loc);
+        }
+    }
+}
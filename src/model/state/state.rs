@@ -1,12 +1,34 @@
-use std::{any::Any, collections::HashMap};
+use std::{
+    any::Any,
+    collections::{HashMap, HashSet},
+};
 
 use crate::model::{
-    calc_utils::navigation_distance::make_navigable_matrix,
+    activity::{ActivityChain, Itinerary},
+    calc_utils::{distance_field::wall_distance_field, navigation_distance::make_navigable_matrix},
+    congestion::CongestionRerouting,
+    counting_line::{CountingLine, LineCrossing},
+    crossing::{Crossing, CrossingStats},
+    door::{Door, DoorState},
+    evacuation::Evacuation,
+    fundamental_diagram::{AreaVisit, FdSample},
+    group::{Group, GroupDemand},
     object::{Object, ObjectType},
-    pedestrian::Pedestrian,
+    pedestrian::{PedStatus, Pedestrian},
+    poi::{DestinationChoice, DestinationSampler, Poi},
+    profile::Profile,
+    route_choice::RouteChoice,
+    scenario::{
+        DensityOutput, FrameOutput, FundamentalDiagramOutput, LosOutput, ObstacleEvent,
+        PopulationMode, Region, Scenario, StreamOutput, TrajectoryOutput, Zone,
+    },
+    service::{ServicePoint, ServiceState},
+    snapshot::Snapshot,
     state::components::*,
+    trip::{TripRecord, TripSummary},
 };
 
+use itertools::iproduct;
 use krabmaga::engine::fields::field::Field;
 use krabmaga::engine::{
     fields::{field_2d::Field2D, sparse_number_grid_2d::SparseNumberGrid2D},
@@ -14,7 +36,13 @@ use krabmaga::engine::{
     schedule::Schedule,
     state::State,
 };
+use krabmaga::rand::{self, Rng};
 use ndarray::Array2;
+use rand_chacha::{rand_core::SeedableRng, ChaCha8Rng};
+
+use crate::system_interface::{
+    frame_writer::FrameWriter, stream_server::StreamServer, trajectory_writer::TrajectoryWriter,
+};
 
 /// Expand the state definition according to your model, for example by having a grid struct field to
 /// store the agents' locations.
@@ -26,10 +54,123 @@ pub struct ModelState {
     pub ped_paths: HashMap<u32, std::vec::IntoIter<Real2D>>,
     pub dim: (f32, f32),
     pub num_agents: u32,
+    pub num_steps: u64,
+    //Number of runs completed so far, used to tell repetitions' outputs apart
+    pub run: u32,
+    //Latest copy of every pedestrian still on the field, written by the agents each step
+    pub active_peds: HashMap<u32, Pedestrian>,
+    pub cell_size: f32,
+    pub step_duration: f32,
+    pub output_dir: String,
+    //Destinations changed during the run, picked up by the agents on their next step
+    pub pending_dests: HashMap<u32, Real2D>,
+    pub obstacle_events: Vec<ObstacleEvent>,
+    //Obstacle cells of the raster, which the grid is rebuilt from whenever closures change
+    pub raster_obstacles: HashSet<Int2D>,
+    //Cells closed by each currently active obstacle event, keyed by event index
+    pub applied_closures: HashMap<usize, Vec<Int2D>>,
+    //Pedestrians waiting for a closure to reopen because no other route exists
+    pub stranded_peds: HashSet<u32>,
+    pub crossings: Vec<Crossing>,
+    pub crossing_stats: Vec<CrossingStats>,
+    //Pedestrians waiting at a kerb, in no order: crossing index and the step they started waiting
+    pub crossing_waits: HashMap<u32, (usize, u64)>,
+    pub doors: Vec<Door>,
+    pub door_states: Vec<DoorState>,
+    pub evacuation: Option<Evacuation>,
+    //Distance to each exit from every cell, indexed like the raster
+    pub exit_distances: Vec<Array2<u32>>,
+    pub exit_choice: HashMap<u32, usize>,
+    //Evacuated pedestrians: id, exit index and the step they got out
+    pub evacuated: Vec<(u32, usize, u64)>,
+    //Pedestrians still inside after each step
+    pub evacuation_curve: Vec<(u64, usize)>,
+    pub trajectory_output: Option<TrajectoryOutput>,
+    pub trajectory_writer: Option<TrajectoryWriter>,
+    pub density_output: Option<DensityOutput>,
+    //Sum of the sampled density maps over the run, and over the current time window
+    pub density_sum: Array2<f32>,
+    pub density_samples: u32,
+    pub window_density_sum: Array2<f32>,
+    pub window_density_samples: u32,
+    pub window_start: u64,
+    //Mean density of each finished time window, with its first step
+    pub density_windows: Vec<(u64, Array2<f32>)>,
+    //Density map of the current step, shared by everything that needs it
+    pub density_cache: Option<(u64, Array2<f32>)>,
+    pub los_output: Option<LosOutput>,
+    //Samples each zone spent in each LOS class, A to F
+    pub zone_los_counts: Vec<[u32; 6]>,
+    //Samples each cell spent in each LOS class, one map per class
+    pub los_cell_counts: Vec<Array2<u32>>,
+    pub los_density_sum: Array2<f32>,
+    pub los_samples: u32,
+    pub counting_lines: Vec<CountingLine>,
+    pub count_interval: u64,
+    pub line_crossings: Vec<LineCrossing>,
+    pub trips: HashMap<u32, TripRecord>,
+    //Trip summary of every run so far, for the summary over repetitions
+    pub trip_summaries: Vec<TripSummary>,
+    pub fd_output: Option<FundamentalDiagramOutput>,
+    pub fd_samples: Vec<FdSample>,
+    //Method B visits in progress, keyed by area index and pedestrian id
+    pub fd_visits: HashMap<(usize, u32), AreaVisit>,
+    pub frame_output: Option<FrameOutput>,
+    pub frame_writer: Option<FrameWriter>,
+    pub stream_output: Option<StreamOutput>,
+    pub stream_server: Option<StreamServer>,
+    //Source of all randomness in a run, so that runs can be reproduced and snapshotted
+    pub rng: ChaCha8Rng,
+    pub snapshot_steps: Vec<u64>,
+    //Snapshot that every run starts from instead of the initial population
+    pub restore_from: Option<Snapshot>,
+    pub population_mode: PopulationMode,
+    //Population and generator as they were when the first run started, for replays
+    pub initial_peds: Vec<Pedestrian>,
+    pub initial_groups: Vec<Group>,
+    pub initial_rng: ChaCha8Rng,
+    //Whether the current population has been scheduled, and so needs replacing on reset
+    pub population_used: bool,
+    pub group_demand: Option<GroupDemand>,
+    pub groups: Vec<Group>,
+    //Group index of every leader and member still walking together
+    pub group_of: HashMap<u32, usize>,
+    //Way of every member catching up with its leader, with the leader's cell it leads to
+    pub catch_up_paths: HashMap<u32, (Int2D, std::vec::IntoIter<Real2D>)>,
+    pub profiles: Vec<Profile>,
+    pub stairs: Vec<Region>,
+    pub route_zones: Vec<Zone>,
+    //Distance in cells from every free cell to the nearest wall, for the profiles' route costs
+    pub wall_distances: Array2<u32>,
+    pub activity_chains: Vec<ActivityChain>,
+    //Itinerary of every pedestrian on an activity chain, except group members
+    pub itineraries: HashMap<u32, Itinerary>,
+    pub initial_itineraries: HashMap<u32, Itinerary>,
+    //Pedestrians dwelling at a stop, with the step they leave again
+    pub dwelling: HashMap<u32, u64>,
+    pub pois: Vec<Poi>,
+    pub destination_choice: Option<DestinationChoice>,
+    pub route_choice: Option<RouteChoice>,
+    //Seed of the perturbations and draws of the route choice model, fixed per population
+    pub route_seed: u64,
+    pub congestion: Option<CongestionRerouting>,
+    pub services: Vec<ServicePoint>,
+    pub service_states: Vec<ServiceState>,
+    //Pedestrians called to a service point, with the steps their service will take
+    pub service_passes: HashMap<u32, u64>,
+    //Queue length and pedestrians being served at every service point after each step's call:
+    //step, service point index, queue length, serving
+    pub queue_lengths: Vec<(u64, usize, usize, usize)>,
 }
 
 impl ModelState {
-    pub fn new(dim: (f32, f32), num_agents: u32, grid: Option<Array2<u8>>) -> ModelState {
+    pub fn new(
+        dim: (f32, f32),
+        num_agents: u32,
+        num_steps: u64,
+        grid: Option<Array2<u8>>,
+        scenario: Scenario,
+    ) -> ModelState {
         let obj_grid;
         //let navigable_object_grid;
         //Make object grid
@@ -43,24 +184,256 @@ impl ModelState {
                 //navigable_object_grid = make_navigable_matrix::<i32, u8>(&obj_grid)
             }
         };
-        //Initialize pedestrian records
-        let peds = make_peds(num_agents, dim, &obj_grid);
+        let mut rng = match scenario.seed {
+            Some(seed) => ChaCha8Rng::seed_from_u64(seed),
+            None => ChaCha8Rng::seed_from_u64(rand::thread_rng().gen()),
+        };
+
+        //Initialize pedestrian records with their profiles; itineraries and groups follow below
+        let destinations = DestinationSampler::new(
+            &scenario.pois,
+            scenario.destination_choice.as_ref(),
+            &obj_grid,
+            scenario.cell_size,
+        );
+        let mut peds = make_peds(num_agents, dim, &obj_grid, destinations.as_ref(), &mut rng);
+        assign_profiles(
+            &mut peds,
+            &scenario.profiles,
+            scenario.cell_size,
+            scenario.step_duration,
+            &mut rng,
+        );
+
+        //Service points whose queue has no place to start would hold their pedestrians forever
+        let services: Vec<ServicePoint> = scenario
+            .services
+            .into_iter()
+            .filter_map(|mut service| {
+                service.queue_room = service.count_queue_room(&obj_grid, num_agents.max(1) as usize);
+                if service.queue_room == 0 {
+                    println!(
+                        "Service point {} rejected: its queue starts off the grid, on an obstacle or on the service point",
+                        service.name
+                    );
+                    return None;
+                }
+                Some(service)
+            })
+            .collect();
+
+        //Lines without length have no side to cross from, nor a width for the specific flow
+        let counting_lines: Vec<CountingLine> = scenario
+            .counting_lines
+            .into_iter()
+            .filter(|line| {
+                if line.length() > 0. {
+                    return true;
+                }
+                println!(
+                    "Counting line {} rejected: its start and end are the same point",
+                    line.name
+                );
+                false
+            })
+            .collect();
 
         //Make field for pedestrians
         let field = make_field(dim);
 
-        //Calculate paths, given pedestrians
-        let ped_paths = make_paths(&peds, &obj_grid);
+        let raster_obstacles: HashSet<Int2D> = iproduct!(0..obj_grid.width, 0..obj_grid.height)
+            .map(|(x, y)| Int2D { x, y })
+            .filter(|cell| obj_grid.get_value(cell).is_some())
+            .collect();
+        let wall_distances = wall_distance_field(&obj_grid);
+        let grid_dim = (obj_grid.height as usize, obj_grid.width as usize);
 
-        ModelState {
+        let mut state = ModelState {
             step: 0,
             peds,
             field,
             obj_grid,
-            ped_paths,
+            //Planned below, once the route costs are in place
+            ped_paths: HashMap::new(),
             dim,
             num_agents,
+            num_steps,
+            run: 0,
+            active_peds: HashMap::new(),
+            cell_size: scenario.cell_size,
+            step_duration: scenario.step_duration,
+            output_dir: scenario.output_dir,
+            pending_dests: HashMap::new(),
+            obstacle_events: scenario.obstacle_events,
+            raster_obstacles,
+            applied_closures: HashMap::new(),
+            stranded_peds: HashSet::new(),
+            crossing_stats: vec![CrossingStats::default(); scenario.crossings.len()],
+            crossings: scenario.crossings,
+            crossing_waits: HashMap::new(),
+            door_states: vec![DoorState::default(); scenario.doors.len()],
+            doors: scenario.doors,
+            evacuation: scenario.evacuation,
+            exit_distances: Vec::new(),
+            exit_choice: HashMap::new(),
+            evacuated: Vec::new(),
+            evacuation_curve: Vec::new(),
+            trajectory_output: scenario.trajectories,
+            trajectory_writer: None,
+            density_output: scenario.density,
+            density_sum: Array2::zeros(grid_dim),
+            density_samples: 0,
+            window_density_sum: Array2::zeros(grid_dim),
+            window_density_samples: 0,
+            window_start: 0,
+            density_windows: Vec::new(),
+            density_cache: None,
+            zone_los_counts: vec![[0; 6]; scenario.los.as_ref().map_or(0, |los| los.zones.len())],
+            los_cell_counts: vec![Array2::zeros(grid_dim); 6],
+            los_output: scenario.los,
+            los_density_sum: Array2::zeros(grid_dim),
+            los_samples: 0,
+            counting_lines,
+            count_interval: scenario.count_interval,
+            line_crossings: Vec::new(),
+            trips: HashMap::new(),
+            trip_summaries: Vec::new(),
+            fd_output: scenario.fundamental_diagram,
+            fd_samples: Vec::new(),
+            fd_visits: HashMap::new(),
+            frame_output: scenario.frames,
+            frame_writer: None,
+            stream_output: scenario.stream,
+            stream_server: None,
+            //Set below, once the population is complete
+            initial_peds: Vec::new(),
+            initial_groups: Vec::new(),
+            initial_rng: rng.clone(),
+            rng,
+            snapshot_steps: scenario.snapshot_steps,
+            restore_from: None,
+            population_mode: scenario.population,
+            population_used: false,
+            group_demand: scenario.groups,
+            groups: Vec::new(),
+            group_of: HashMap::new(),
+            catch_up_paths: HashMap::new(),
+            profiles: scenario.profiles,
+            stairs: scenario.stairs,
+            route_zones: scenario.route_zones,
+            wall_distances,
+            activity_chains: scenario.activity_chains,
+            itineraries: HashMap::new(),
+            initial_itineraries: HashMap::new(),
+            dwelling: HashMap::new(),
+            pois: scenario.pois,
+            destination_choice: scenario.destination_choice,
+            route_choice: scenario.route_choice,
+            route_seed: 0,
+            congestion: scenario.congestion,
+            service_states: vec![ServiceState::default(); services.len()],
+            services,
+            service_passes: HashMap::new(),
+            queue_lengths: Vec::new(),
+        };
+
+        //Groups take their leaders' first stops as destinations, so itineraries come first
+        state.draw_route_seed();
+        state.draw_itineraries();
+        state.form_groups();
+        state.initial_peds = state.peds.clone();
+        state.initial_groups = state.groups.clone();
+        state.initial_itineraries = state.itineraries.clone();
+        state.initial_rng = state.rng.clone();
+        state.plan_population_paths();
+        state
+    }
+
+    /// Whether a pedestrian standing at `loc` may move onto the next point of its path this step.
+    /// Every gate is checked before any of them counts the pedestrian through, so that one
+    /// held back by a gate does not use up a passage or a call at another.
+    pub fn may_advance(&mut self, id: u32, loc: Real2D, next: Real2D) -> bool {
+        let crossing = self.crossing_entered(loc, next);
+        let door = self.door_entered(loc, next);
+        let service = self.service_entered(id, loc, next);
+
+        if let Some(idx) = door.filter(|idx| !self.door_open(id, *idx)) {
+            self.join_door_queue(id, idx);
+            return false;
+        }
+        if let Some(idx) = service.filter(|_| !self.service_open(id)) {
+            self.join_queue(id, idx);
+            return false;
+        }
+        //Crossing against the signal is a random decision, so it is only taken once
+        //nothing else holds the pedestrian back
+        let violation = match crossing {
+            Some(idx) if !self.crossing_open(idx) => {
+                if !self.crosses_against_signal(id, idx) {
+                    return false;
+                }
+                true
+            }
+            _ => false,
+        };
+
+        if let Some(idx) = crossing {
+            self.pass_crossing(id, idx, violation);
+        }
+        if let Some(idx) = door {
+            self.pass_door(id, idx);
+        }
+        if service.is_some() {
+            self.pass_service(id);
         }
+        true
+    }
+
+    /// Called when a pedestrian reaches its destination
+    pub fn record_arrival(&mut self, ped: &Pedestrian) {
+        if let Some(active) = self.active_peds.get_mut(&ped.id) {
+            active.status = PedStatus::Arrived;
+        }
+        self.record_evacuation(ped.id);
+        self.record_trip_arrival(ped.id);
+        self.release_group(ped);
+    }
+
+    /// Called once the last step of a run has been taken
+    pub fn end_of_run(&mut self) {
+        self.report_crossings();
+        self.report_doors();
+        self.report_evacuation();
+        self.finish_trajectories();
+        self.finish_frames();
+        self.write_density_maps();
+        self.write_los();
+        self.write_line_counts();
+        self.write_trips();
+        self.write_fundamental_diagram();
+        self.write_services();
+        self.run += 1;
+    }
+
+    /// Clear what the previous run left behind and put back, or redraw, the population.
+    /// `simulate!` only calls `init` before each repetition, so `init` starts with this.
+    pub fn reset_run(&mut self) {
+        self.step = 0;
+        self.field = make_field(self.dim);
+        self.reset_crossings();
+        self.door_states = vec![DoorState::default(); self.doors.len()];
+        self.evacuated.clear();
+        self.evacuation_curve.clear();
+        self.active_peds.clear();
+        self.catch_up_paths.clear();
+        self.finish_trajectories();
+        self.finish_frames();
+        self.reset_density();
+        self.reset_los();
+        self.line_crossings.clear();
+        self.reset_fundamental_diagram();
+        self.reset_services();
+        self.reset_population();
     }
 
     // pub fn get_obstacle(&self, loc: &Int2D) -> Option<Vec<Object>> {
@@ -75,18 +448,33 @@ impl State for ModelState {
     /// schedule step.
     fn update(&mut self, _step: u64) {
         self.field.lazy_update();
+        self.apply_obstacle_events();
+        self.refill_doors();
+        self.serve_queues();
+        self.reroute_congested();
+
+        if let Some(interval) = self.evacuation.as_ref().and_then(|e| e.update_interval) {
+            if interval > 0 && self.step > 0 && self.step.is_multiple_of(interval) {
+                self.update_exit_choices();
+            }
+        }
     }
 
     /// Put the code that should be executed to reset simulation state
     fn reset(&mut self) {
-        self.step = 0;
-        self.field = make_field(self.dim);
+        self.reset_run();
     }
 
     /// Put the code that should be executed to initialize simulation:
     /// Agent creation and schedule set-up
     fn init(&mut self, schedule: &mut Schedule) {
+        self.reset_run();
+        if let Some(snapshot) = self.restore_from.clone() {
+            self.apply_snapshot(snapshot, schedule);
+            return;
+        }
         self.step = 0;
+        self.population_used = true;
 
         let peds_iter = self.peds.iter();
 
@@ -94,7 +482,7 @@ impl State for ModelState {
             schedule.schedule_repeating(Box::new(*agent), 0., 0);
         }
 
-        self.obj_grid.update();
+        self.start_trips();
         //println!("{:?}", self.ped_paths);
     }
 
@@ -114,6 +502,26 @@ impl State for ModelState {
         self
     }
     fn after_step(&mut self, _schedule: &mut Schedule) {
-        self.step += 1
+        self.step += 1;
+        self.record_trajectories();
+        self.record_line_crossings();
+        self.record_walked();
+        self.record_fundamental_diagram();
+        self.active_peds
+            .retain(|_, ped| ped.status != PedStatus::Arrived);
+        self.record_density();
+        self.record_frame();
+        self.record_stream();
+        self.record_los();
+        self.record_evacuation_curve();
+        self.record_snapshot();
+        if self.step == self.num_steps || self.evacuation_complete() {
+            self.end_of_run();
+        }
+    }
+
+    fn end_condition(&mut self, _schedule: &mut Schedule) -> bool {
+        //Runs resumed from a snapshot reach num_steps before the schedule does
+        self.step >= self.num_steps || self.evacuation_complete()
     }
 }
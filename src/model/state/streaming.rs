@@ -0,0 +1,92 @@
+use crate::model::{scenario::StreamFormat, state::state::ModelState};
+use crate::system_interface::stream_server::StreamServer;
+
+use serde_json::{json, Value};
+use tungstenite::Message;
+
+impl ModelState {
+    /// Send the position, velocity and status of every pedestrian on the field to the
+    /// connected viewers, every `interval` steps. Positions are in metres.
+    pub fn record_stream(&mut self) {
+        let Some(output) = &self.stream_output else {
+            return;
+        };
+        if !self.step.is_multiple_of(output.interval.max(1)) {
+            return;
+        }
+
+        //The endpoint stays open over all runs, so viewers see the repetitions one after another
+        if self.stream_server.is_none() {
+            match StreamServer::bind(&output.address) {
+                Ok(server) => self.stream_server = Some(server),
+                Err(e) => {
+                    println!("Failed to open stream on {}: {}", output.address, e);
+                    self.stream_output = None;
+                    return;
+                }
+            }
+        }
+        let Some(server) = &self.stream_server else {
+            return;
+        };
+        if !server.has_clients() {
+            return;
+        }
+
+        let speed_factor = self.cell_size / self.step_duration;
+        let mut peds: Vec<_> = self.active_peds.values().collect();
+        peds.sort_by_key(|ped| ped.id);
+
+        let message = match output.format {
+            StreamFormat::Json => {
+                let agents: Vec<Value> = peds
+                    .iter()
+                    .map(|ped| {
+                        let (x, y) = (ped.loc.x * self.cell_size, ped.loc.y * self.cell_size);
+                        let mut agent = json!({
+                            "id": ped.id,
+                            "x": x,
+                            "y": y,
+                            "vx": ped.last_d.x * speed_factor,
+                            "vy": ped.last_d.y * speed_factor,
+                            "state": ped.status.to_string(),
+                        });
+                        if let Some(origin) = &output.origin {
+                            let (lon, lat) = origin.lon_lat(x, y);
+                            agent["lon"] = json!(lon);
+                            agent["lat"] = json!(lat);
+                        }
+                        agent
+                    })
+                    .collect();
+                let frame = json!({
+                    "run": self.run,
+                    "step": self.step,
+                    "time": self.step as f32 * self.step_duration,
+                    "agents": agents,
+                });
+                Message::Text(frame.to_string())
+            }
+            StreamFormat::Binary => {
+                let mut frame = Vec::with_capacity(16 + peds.len() * 21);
+                frame.extend(self.run.to_le_bytes());
+                frame.extend(self.step.to_le_bytes());
+                frame.extend((peds.len() as u32).to_le_bytes());
+                for ped in peds {
+                    frame.extend(ped.id.to_le_bytes());
+                    for value in [
+                        ped.loc.x * self.cell_size,
+                        ped.loc.y * self.cell_size,
+                        ped.last_d.x * speed_factor,
+                        ped.last_d.y * speed_factor,
+                    ] {
+                        frame.extend(value.to_le_bytes());
+                    }
+                    frame.push(ped.status.code());
+                }
+                Message::Binary(frame)
+            }
+        };
+        server.broadcast(message);
+    }
+}
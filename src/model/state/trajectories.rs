@@ -0,0 +1,64 @@
+use crate::model::state::state::ModelState;
+use crate::system_interface::trajectory_writer::{TrajectoryRow, TrajectoryWriter};
+
+impl ModelState {
+    /// Write the position, velocity and status of every pedestrian still on the field,
+    /// every `interval` steps.
+    pub fn record_trajectories(&mut self) {
+        let Some(output) = &self.trajectory_output else {
+            return;
+        };
+        let (interval, parquet) = (output.interval.max(1), output.parquet);
+        if !self.step.is_multiple_of(interval) {
+            return;
+        }
+
+        if self.trajectory_writer.is_none() {
+            match TrajectoryWriter::new(
+                &self.output_dir,
+                &format!("trajectories_{}", self.run),
+                parquet,
+            ) {
+                Ok(writer) => self.trajectory_writer = Some(writer),
+                Err(e) => {
+                    println!("Failed to open trajectory output: {}", e);
+                    self.trajectory_output = None;
+                    return;
+                }
+            }
+        }
+
+        let speed_factor = self.cell_size / self.step_duration;
+        let mut rows: Vec<TrajectoryRow> = self
+            .active_peds
+            .values()
+            .map(|ped| TrajectoryRow {
+                step: self.step,
+                time: self.step as f32 * self.step_duration,
+                id: ped.id,
+                x: ped.loc.x * self.cell_size,
+                y: ped.loc.y * self.cell_size,
+                vx: ped.last_d.x * speed_factor,
+                vy: ped.last_d.y * speed_factor,
+                //The obstacle grid has a single level
+                level: 0,
+                state: ped.status,
+            })
+            .collect();
+        rows.sort_by_key(|row| row.id);
+
+        if let Some(writer) = self.trajectory_writer.as_mut() {
+            if let Err(e) = writer.write(&rows) {
+                println!("Failed to write trajectories: {}", e);
+            }
+        }
+    }
+
+    pub fn finish_trajectories(&mut self) {
+        if let Some(writer) = self.trajectory_writer.take() {
+            if let Err(e) = writer.finish() {
+                println!("Failed to finish trajectory output: {}", e);
+            }
+        }
+    }
+}
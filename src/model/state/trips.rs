@@ -0,0 +1,152 @@
+use crate::model::{
+    state::state::ModelState,
+    trip::{RepetitionSummary, TripRecord, TripSummary},
+};
+use crate::system_interface::output_writer::{output_path, write_csv};
+
+use std::fs::File;
+use std::io::BufWriter;
+
+//A path holds the origin but not the destination, and the first move is spent on the origin,
+//so an unhindered walk takes one move more than the path length
+fn free_flow_steps(moves: usize, speed: f32) -> f32 {
+    ((moves + 1) as f32 / speed.max(f32::EPSILON)).ceil()
+}
+
+impl ModelState {
+    /// Open a trip record for every pedestrian with a planned path
+    pub fn start_trips(&mut self) {
+        self.trips.clear();
+        for ped in self.peds.iter() {
+            let Some(path) = self.ped_paths.get(&ped.id) else {
+                continue;
+            };
+            let moves = path.len();
+            let trip = TripRecord {
+                id: ped.id,
+                profile: self.profile_name(ped),
+                departure_step: self.step,
+                planned_length: moves as f32 * self.cell_size,
+                free_flow_time: free_flow_steps(moves, ped.speed) * self.step_duration,
+                ..Default::default()
+            };
+            self.trips.insert(ped.id, trip);
+        }
+    }
+
+    /// Add the moves of the step just taken to the walked length of each trip
+    pub fn record_walked(&mut self) {
+        for ped in self.active_peds.values() {
+            if let Some(trip) = self.trips.get_mut(&ped.id) {
+                trip.walked_length +=
+                    (ped.last_d.x.powi(2) + ped.last_d.y.powi(2)).sqrt() * self.cell_size;
+            }
+        }
+    }
+
+    /// Add a stop and the next leg of an activity chain to a trip
+    pub fn extend_trip(&mut self, id: u32, moves: usize, speed: f32, dwell_steps: u64) {
+        if let Some(trip) = self.trips.get_mut(&id) {
+            trip.stops += 1;
+            trip.dwell_time += dwell_steps as f32 * self.step_duration;
+            trip.planned_length += moves as f32 * self.cell_size;
+            trip.free_flow_time += free_flow_steps(moves, speed) * self.step_duration;
+        }
+    }
+
+    pub fn count_replan(&mut self, id: u32) {
+        if let Some(trip) = self.trips.get_mut(&id) {
+            trip.replans += 1;
+        }
+    }
+
+    pub fn record_trip_arrival(&mut self, id: u32) {
+        if let Some(trip) = self.trips.get_mut(&id) {
+            //Arrivals happen during the current step, so count it as taken
+            let arrival_step = self.step + 1;
+            let travel_time = (arrival_step - trip.departure_step) as f32 * self.step_duration;
+            trip.arrival_step = Some(arrival_step);
+            trip.travel_time = Some(travel_time);
+            //Time spent at stops is not a delay
+            trip.delay = Some(travel_time - trip.free_flow_time - trip.dwell_time);
+        }
+    }
+
+    /// Write the per-trip records and the run summary, and update the summary over repetitions
+    pub fn write_trips(&mut self) {
+        let mut records: Vec<TripRecord> = self.trips.values().cloned().collect();
+        records.sort_by_key(|record| record.id);
+
+        let optional = |value: Option<String>| value.unwrap_or_default();
+        let rows = records.iter().map(|r| {
+            format!(
+                "{},{},{},{},{},{},{},{},{},{},{},{}",
+                r.id,
+                r.profile,
+                r.departure_step,
+                optional(r.arrival_step.map(|v| v.to_string())),
+                r.planned_length,
+                r.walked_length,
+                r.free_flow_time,
+                optional(r.travel_time.map(|v| v.to_string())),
+                optional(r.delay.map(|v| v.to_string())),
+                r.replans,
+                r.stops,
+                r.dwell_time
+            )
+        });
+        if let Err(e) = output_path(&self.output_dir, &format!("trips_{}.csv", self.run)).and_then(
+            |path| {
+                write_csv(
+                    &path,
+                    "id,profile,departure_step,arrival_step,planned_length,walked_length,free_flow_time,travel_time,delay,replans,stops,dwell_time",
+                    rows,
+                )
+            },
+        ) {
+            println!("Failed to write trips: {}", e);
+        }
+
+        let summary = TripSummary::from_records(self.run, &records);
+        println!(
+            "{} of {} trips completed, mean travel time {:.1} s, mean delay {:.1} s",
+            summary.arrived, summary.trips, summary.mean_travel_time, summary.mean_delay
+        );
+        self.write_json(&format!("trips_{}_summary.json", self.run), &summary);
+        if !self.profiles.is_empty() {
+            let profile_summaries = self.profile_summaries(&records);
+            for (name, profile_summary) in profile_summaries.iter() {
+                println!(
+                    "  {}: {} of {} trips completed, mean travel time {:.1} s, mean delay {:.1} s",
+                    name,
+                    profile_summary.arrived,
+                    profile_summary.trips,
+                    profile_summary.mean_travel_time,
+                    profile_summary.mean_delay
+                );
+            }
+            self.write_json(
+                &format!("trips_{}_profiles.json", self.run),
+                &profile_summaries,
+            );
+        }
+
+        self.trip_summaries.push(summary);
+        let repetitions = RepetitionSummary::from_runs(&self.trip_summaries);
+        self.write_json("trips_summary.json", &repetitions);
+    }
+
+    /// Write a value as pretty JSON to the output directory, reporting failures
+    pub fn write_json<T: serde::Serialize>(&self, file_name: &str, value: &T) {
+        let result = output_path(&self.output_dir, file_name)
+            .and_then(File::create)
+            .map_err(anyhow::Error::from)
+            .and_then(|file| {
+                serde_json::to_writer_pretty(BufWriter::new(file), value)
+                    .map_err(anyhow::Error::from)
+            });
+        if let Err(e) = result {
+            println!("Failed to write {}: {}", file_name, e);
+        }
+    }
+}
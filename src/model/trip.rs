@@ -0,0 +1,130 @@
+use serde::{Deserialize, Serialize};
+
+/// Travel record of one pedestrian, with lengths in metres and times in seconds
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct TripRecord {
+    pub id: u32,
+    //Name of the pedestrian's profile, empty without profiles
+    #[serde(default)]
+    pub profile: String,
+    pub departure_step: u64,
+    pub arrival_step: Option<u64>,
+    pub planned_length: f32,
+    pub walked_length: f32,
+    pub free_flow_time: f32,
+    pub travel_time: Option<f32>,
+    pub delay: Option<f32>,
+    pub replans: u32,
+    //Stops made along an activity chain, and the time spent at them
+    #[serde(default)]
+    pub stops: u32,
+    #[serde(default)]
+    pub dwell_time: f32,
+}
+
+/// Aggregate of the trip records of one run
+#[derive(Clone, Debug, Default, Serialize)]
+pub struct TripSummary {
+    pub run: u32,
+    pub trips: usize,
+    pub arrived: usize,
+    pub mean_planned_length: f32,
+    pub mean_walked_length: f32,
+    pub mean_free_flow_time: f32,
+    pub mean_travel_time: f32,
+    pub median_travel_time: f32,
+    pub p90_travel_time: f32,
+    pub mean_delay: f32,
+    pub mean_replans: f32,
+    pub mean_dwell_time: f32,
+}
+
+fn mean(values: &[f32]) -> f32 {
+    if values.is_empty() {
+        return 0.;
+    }
+    values.iter().sum::<f32>() / values.len() as f32
+}
+
+//Nearest-rank percentile of sorted values
+fn percentile(sorted: &[f32], p: f32) -> f32 {
+    if sorted.is_empty() {
+        return 0.;
+    }
+    let rank = ((p * sorted.len() as f32).ceil() as usize).clamp(1, sorted.len());
+    sorted[rank - 1]
+}
+
+impl TripSummary {
+    pub fn from_records(run: u32, records: &[TripRecord]) -> TripSummary {
+        let arrived: Vec<&TripRecord> = records
+            .iter()
+            .filter(|record| record.arrival_step.is_some())
+            .collect();
+        let mut travel_times: Vec<f32> = arrived.iter().filter_map(|r| r.travel_time).collect();
+        travel_times.sort_by(|a, b| a.total_cmp(b));
+        let delays: Vec<f32> = arrived.iter().filter_map(|r| r.delay).collect();
+
+        TripSummary {
+            run,
+            trips: records.len(),
+            arrived: arrived.len(),
+            mean_planned_length: mean(
+                &records
+                    .iter()
+                    .map(|r| r.planned_length)
+                    .collect::<Vec<f32>>(),
+            ),
+            mean_walked_length: mean(
+                &records
+                    .iter()
+                    .map(|r| r.walked_length)
+                    .collect::<Vec<f32>>(),
+            ),
+            mean_free_flow_time: mean(
+                &records
+                    .iter()
+                    .map(|r| r.free_flow_time)
+                    .collect::<Vec<f32>>(),
+            ),
+            mean_travel_time: mean(&travel_times),
+            median_travel_time: percentile(&travel_times, 0.5),
+            p90_travel_time: percentile(&travel_times, 0.9),
+            mean_delay: mean(&delays),
+            mean_replans: mean(
+                &records
+                    .iter()
+                    .map(|r| r.replans as f32)
+                    .collect::<Vec<f32>>(),
+            ),
+            mean_dwell_time: mean(&records.iter().map(|r| r.dwell_time).collect::<Vec<f32>>()),
+        }
+    }
+}
+
+/// Per-run summaries over all repetitions, with the mean over runs of the main indicators
+#[derive(Clone, Debug, Default, Serialize)]
+pub struct RepetitionSummary {
+    pub runs: Vec<TripSummary>,
+    pub mean_arrived: f32,
+    pub mean_travel_time: f32,
+    pub mean_delay: f32,
+    pub mean_replans: f32,
+}
+
+impl RepetitionSummary {
+    pub fn from_runs(runs: &[TripSummary]) -> RepetitionSummary {
+        RepetitionSummary {
+            runs: runs.to_vec(),
+            mean_arrived: mean(&runs.iter().map(|r| r.arrived as f32).collect::<Vec<f32>>()),
+            mean_travel_time: mean(
+                &runs
+                    .iter()
+                    .map(|r| r.mean_travel_time)
+                    .collect::<Vec<f32>>(),
+            ),
+            mean_delay: mean(&runs.iter().map(|r| r.mean_delay).collect::<Vec<f32>>()),
+            mean_replans: mean(&runs.iter().map(|r| r.mean_replans).collect::<Vec<f32>>()),
+        }
+    }
+}
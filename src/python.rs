@@ -0,0 +1,203 @@
+use crate::{
+    load_obstacle_grid, model::trip::TripRecord, ModelState, Scenario, Simulation, TripSummary,
+};
+
+use ndarray::{Array1, Array2};
+use numpy::{IntoPyArray, PyArray1, PyArray2};
+use pyo3::exceptions::PyValueError;
+use pyo3::prelude::*;
+use pyo3::types::PyDict;
+
+const TRAJECTORY_COLUMNS: usize = 6;
+
+fn value_error(e: impl std::fmt::Display) -> PyErr {
+    PyValueError::new_err(e.to_string())
+}
+
+//Rows of equal length as a 2D array with one row per entry
+fn rows_to_array<'py, const N: usize>(py: Python<'py>, rows: &[[f32; N]]) -> &'py PyArray2<f32> {
+    let flat: Vec<f32> = rows.iter().flatten().copied().collect();
+    Array2::from_shape_vec((rows.len(), N), flat)
+        .expect("Every row has N columns")
+        .into_pyarray(py)
+}
+
+fn column<'py>(
+    py: Python<'py>,
+    records: &[TripRecord],
+    value: fn(&TripRecord) -> f32,
+) -> &'py PyArray1<f32> {
+    Array1::from_iter(records.iter().map(value)).into_pyarray(py)
+}
+
+/// A single simulation run, stepped from Python. Positions and lengths are in metres,
+/// times in seconds; the pedestrian status is coded 0 walking, 1 waiting, 2 arrived,
+/// 3 dwelling.
+#[pyclass(unsendable, name = "Simulation")]
+pub struct PySimulation {
+    simulation: Simulation,
+    //step, id, x, y, vx, vy of every pedestrian on the field after every step taken
+    trajectory: Vec<[f32; TRAJECTORY_COLUMNS]>,
+}
+
+impl PySimulation {
+    fn state(&self) -> &ModelState {
+        &self.simulation.state
+    }
+
+    fn record_step(&mut self) {
+        let state = &self.simulation.state;
+        let speed_factor = state.cell_size / state.step_duration;
+        let mut rows: Vec<[f32; TRAJECTORY_COLUMNS]> = state
+            .active_peds
+            .values()
+            .map(|ped| {
+                [
+                    state.step as f32,
+                    ped.id as f32,
+                    ped.loc.x * state.cell_size,
+                    ped.loc.y * state.cell_size,
+                    ped.last_d.x * speed_factor,
+                    ped.last_d.y * speed_factor,
+                ]
+            })
+            .collect();
+        rows.sort_by(|a, b| a[1].total_cmp(&b[1]));
+        self.trajectory.extend(rows);
+    }
+
+    fn trip_records(&self) -> Vec<TripRecord> {
+        let mut records: Vec<TripRecord> = self.state().trips.values().cloned().collect();
+        records.sort_by_key(|record| record.id);
+        records
+    }
+}
+
+#[pymethods]
+impl PySimulation {
+    /// Set up a run on the obstacle raster (an open 400 x 400 grid if empty) with `num_agents`
+    /// pedestrians, ending after `steps` steps. `scenario` is the JSON text of a scenario file.
+    #[new]
+    #[pyo3(signature = (raster = "", num_agents = 100, steps = 1000, scenario = None))]
+    fn new(raster: &str, num_agents: u32, steps: u64, scenario: Option<&str>) -> PyResult<Self> {
+        let (dim, grid) = load_obstacle_grid(raster).map_err(value_error)?;
+        let scenario: Scenario = match scenario {
+            Some(json) => serde_json::from_str(json).map_err(value_error)?,
+            None => Scenario::default(),
+        };
+        let state = ModelState::new(dim, num_agents, steps, grid, scenario);
+        Ok(PySimulation {
+            simulation: Simulation::new(state),
+            trajectory: Vec::new(),
+        })
+    }
+
+    /// Take up to `n` steps. Returns whether the run goes on.
+    #[pyo3(signature = (n = 1))]
+    fn run(&mut self, n: u64) -> bool {
+        for _ in 0..n {
+            if self.simulation.is_finished() {
+                break;
+            }
+            self.simulation.step();
+            self.record_step();
+        }
+        !self.simulation.is_finished()
+    }
+
+    #[getter]
+    fn step(&self) -> u64 {
+        self.state().step
+    }
+
+    #[getter]
+    fn finished(&self) -> bool {
+        self.simulation.is_finished()
+    }
+
+    /// Pedestrians on the field, one row each: id, x, y, status
+    fn positions<'py>(&self, py: Python<'py>) -> &'py PyArray2<f32> {
+        let state = self.state();
+        let mut rows: Vec<[f32; 4]> = state
+            .active_peds
+            .values()
+            .map(|ped| {
+                [
+                    ped.id as f32,
+                    ped.loc.x * state.cell_size,
+                    ped.loc.y * state.cell_size,
+                    ped.status.code() as f32,
+                ]
+            })
+            .collect();
+        rows.sort_by(|a, b| a[0].total_cmp(&b[0]));
+        rows_to_array(py, &rows)
+    }
+
+    /// Every position recorded so far, one row per pedestrian and step: step, id, x, y, vx, vy
+    fn trajectories<'py>(&self, py: Python<'py>) -> &'py PyArray2<f32> {
+        rows_to_array(py, &self.trajectory)
+    }
+
+    /// Per-trip records as a dict of arrays; arrival, travel time and delay are NaN until arrival
+    fn trips<'py>(&self, py: Python<'py>) -> PyResult<&'py PyDict> {
+        let records = self.trip_records();
+        let trips = PyDict::new(py);
+        trips.set_item("id", column(py, &records, |r| r.id as f32))?;
+        trips.set_item(
+            "profile",
+            records
+                .iter()
+                .map(|r| r.profile.clone())
+                .collect::<Vec<String>>(),
+        )?;
+        trips.set_item(
+            "departure_step",
+            column(py, &records, |r| r.departure_step as f32),
+        )?;
+        trips.set_item(
+            "arrival_step",
+            column(py, &records, |r| {
+                r.arrival_step.map_or(f32::NAN, |s| s as f32)
+            }),
+        )?;
+        trips.set_item("planned_length", column(py, &records, |r| r.planned_length))?;
+        trips.set_item("walked_length", column(py, &records, |r| r.walked_length))?;
+        trips.set_item("free_flow_time", column(py, &records, |r| r.free_flow_time))?;
+        trips.set_item(
+            "travel_time",
+            column(py, &records, |r| r.travel_time.unwrap_or(f32::NAN)),
+        )?;
+        trips.set_item(
+            "delay",
+            column(py, &records, |r| r.delay.unwrap_or(f32::NAN)),
+        )?;
+        trips.set_item("replans", column(py, &records, |r| r.replans as f32))?;
+        trips.set_item("stops", column(py, &records, |r| r.stops as f32))?;
+        trips.set_item("dwell_time", column(py, &records, |r| r.dwell_time))?;
+        Ok(trips)
+    }
+
+    /// Trip statistics of the run so far
+    fn summary<'py>(&self, py: Python<'py>) -> PyResult<&'py PyDict> {
+        let summary = TripSummary::from_records(self.state().run, &self.trip_records());
+        let metrics = PyDict::new(py);
+        metrics.set_item("step", self.state().step)?;
+        metrics.set_item("trips", summary.trips)?;
+        metrics.set_item("arrived", summary.arrived)?;
+        metrics.set_item("mean_planned_length", summary.mean_planned_length)?;
+        metrics.set_item("mean_walked_length", summary.mean_walked_length)?;
+        metrics.set_item("mean_travel_time", summary.mean_travel_time)?;
+        metrics.set_item("median_travel_time", summary.median_travel_time)?;
+        metrics.set_item("p90_travel_time", summary.p90_travel_time)?;
+        metrics.set_item("mean_delay", summary.mean_delay)?;
+        metrics.set_item("mean_replans", summary.mean_replans)?;
+        Ok(metrics)
+    }
+}
+
+#[pymodule]
+fn pedestrian_sim(_py: Python, m: &PyModule) -> PyResult<()> {
+    m.add_class::<PySimulation>()?;
+    Ok(())
+}
@@ -0,0 +1,294 @@
+//! Local HTTP/JSON API for submitting scenarios and controlling their runs.
+//!
+//! | Route                          | Action                                                 |
+//! |--------------------------------|--------------------------------------------------------|
+//! | `POST /runs`                   | submit `{raster, num_agents, steps, scenario}`         |
+//! | `GET /runs`                    | status of every run                                    |
+//! | `GET /runs/{id}`               | status: step, num_steps, running, finished             |
+//! | `POST /runs/{id}/start`        | step continuously, `interval_ms` apart                 |
+//! | `POST /runs/{id}/pause`        | stop stepping                                          |
+//! | `POST /runs/{id}/step`         | take `steps` steps (or `?n=`), one if neither is given |
+//! | `GET /runs/{id}/agents`        | positions and velocities in metres                     |
+//! | `GET /runs/{id}/metrics`       | trip statistics so far                                 |
+//! | `GET /runs/{id}/outputs`       | files written by the run                               |
+//! | `GET /runs/{id}/outputs/{file}`| download one of them                                   |
+//! | `DELETE /runs/{id}`            | stop and forget the run                                |
+
+pub mod worker;
+
+use crate::model::scenario::Scenario;
+use crate::system_interface::object_grid_loader::load_obstacle_grid;
+use worker::{RunClient, RunCommand, RunHandle, RunSetup};
+
+use anyhow::{anyhow, Error};
+use serde::Deserialize;
+use serde_json::{json, Value};
+use std::collections::BTreeMap;
+use std::fs::{self, File};
+use std::path::Path;
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::thread;
+use tiny_http::{Header, Method, Request, Response, Server};
+
+/// Body of `POST /runs`
+#[derive(Deserialize)]
+struct RunRequest {
+    //Obstacle raster on the server's file system; an open 400 x 400 grid if empty
+    #[serde(default)]
+    raster: String,
+    #[serde(default = "default_num_agents")]
+    num_agents: u32,
+    #[serde(default = "default_steps")]
+    steps: u64,
+    #[serde(default)]
+    scenario: Option<Value>,
+}
+
+fn default_num_agents() -> u32 {
+    100
+}
+
+fn default_steps() -> u64 {
+    1000
+}
+
+/// Body of the start and step routes
+#[derive(Deserialize, Default)]
+struct ControlRequest {
+    #[serde(default)]
+    interval_ms: Option<u64>,
+    #[serde(default)]
+    steps: Option<u64>,
+}
+
+type Reply = Response<std::io::Cursor<Vec<u8>>>;
+
+fn header(field: &str, value: &str) -> Header {
+    Header::from_bytes(field.as_bytes(), value.as_bytes()).expect("Headers are plain ASCII")
+}
+
+fn json_reply(status: u16, body: Value) -> Reply {
+    Response::from_string(body.to_string())
+        .with_status_code(status)
+        .with_header(header("Content-Type", "application/json"))
+        .with_header(header("Access-Control-Allow-Origin", "*"))
+}
+
+fn error_reply(status: u16, message: impl std::fmt::Display) -> Reply {
+    json_reply(status, json!({ "error": message.to_string() }))
+}
+
+//Only plain file names inside the run's output directory can be downloaded
+fn safe_file_name(name: &str) -> bool {
+    !name.is_empty() && !name.contains('/') && !name.contains('\\') && !name.contains("..")
+}
+
+fn read_body<T: for<'de> Deserialize<'de> + Default>(request: &mut Request) -> Result<T, Error> {
+    let mut body = String::new();
+    request.as_reader().read_to_string(&mut body)?;
+    match body.trim().is_empty() {
+        true => Ok(T::default()),
+        false => Ok(serde_json::from_str(&body)?),
+    }
+}
+
+fn query_value(query: &str, key: &str) -> Option<u64> {
+    query
+        .split('&')
+        .filter_map(|pair| pair.split_once('='))
+        .find(|(name, _)| *name == key)
+        .and_then(|(_, value)| value.parse().ok())
+}
+
+/// The runs submitted to the server, each stepped on its own worker thread
+struct Runs {
+    output_dir: String,
+    next_id: u32,
+    runs: BTreeMap<u32, RunHandle>,
+}
+
+impl Runs {
+    fn add(&mut self, mut setup: RunSetup) -> u32 {
+        let id = self.next_id;
+        setup.scenario.output_dir = Path::new(&self.output_dir)
+            .join(format!("run_{}", id))
+            .to_string_lossy()
+            .to_string();
+        self.next_id += 1;
+        self.runs.insert(id, RunHandle::spawn(id, setup));
+        id
+    }
+
+    fn client(&self, id: u32) -> Option<RunClient> {
+        self.runs.get(&id).map(|run| run.client())
+    }
+
+    fn outputs(&self, id: u32) -> Option<Vec<String>> {
+        let mut files: Vec<String> = match fs::read_dir(&self.runs.get(&id)?.output_dir) {
+            Ok(entries) => entries
+                .filter_map(|entry| entry.ok())
+                .filter(|entry| entry.path().is_file())
+                .map(|entry| entry.file_name().to_string_lossy().to_string())
+                .collect(),
+            Err(_) => Vec::new(),
+        };
+        files.sort();
+        Some(files)
+    }
+}
+
+//Requests are answered on their own threads, so the lock is only held to look runs up and
+//never while a worker is busy with a command
+fn lock(runs: &Mutex<Runs>) -> MutexGuard<'_, Runs> {
+    runs.lock().unwrap_or_else(|poisoned| poisoned.into_inner())
+}
+
+fn submit(runs: &Mutex<Runs>, request: RunRequest) -> Result<Value, Error> {
+    let (dim, grid) = load_obstacle_grid(&request.raster)?;
+    let scenario: Scenario = match request.scenario {
+        Some(scenario) => serde_json::from_value(scenario)?,
+        None => Scenario::default(),
+    };
+    let setup = RunSetup {
+        dim,
+        grid,
+        num_agents: request.num_agents,
+        num_steps: request.steps,
+        scenario,
+    };
+    let id = lock(runs).add(setup);
+    Ok(json!({ "id": id }))
+}
+
+fn handle(runs: &Mutex<Runs>, mut request: Request) {
+    let url = request.url().to_string();
+    let (path, query) = url.split_once('?').unwrap_or((&url, ""));
+    let segments: Vec<&str> = path.split('/').filter(|s| !s.is_empty()).collect();
+    let method = request.method().clone();
+
+    //Downloads are streamed from the file instead of a JSON body
+    if let (Method::Get, ["runs", id, "outputs", file]) = (&method, segments.as_slice()) {
+        let file_path = id
+            .parse::<u32>()
+            .ok()
+            .filter(|_| safe_file_name(file))
+            .and_then(|id| {
+                lock(runs)
+                    .runs
+                    .get(&id)
+                    .map(|run| Path::new(&run.output_dir).join(file))
+            });
+        if let Some(Ok(output)) = file_path.map(File::open) {
+            let response = Response::from_file(output)
+                .with_header(header("Content-Type", "application/octet-stream"))
+                .with_header(header("Access-Control-Allow-Origin", "*"));
+            if let Err(e) = request.respond(response) {
+                println!("Could not send {}: {}", file, e);
+            }
+            return;
+        }
+    }
+
+    let reply = route(runs, &method, &segments, query, &mut request);
+    if let Err(e) = request.respond(reply) {
+        println!("Could not answer {} {}: {}", method, url, e);
+    }
+}
+
+fn route(
+    runs: &Mutex<Runs>,
+    method: &Method,
+    segments: &[&str],
+    query: &str,
+    request: &mut Request,
+) -> Reply {
+    match (method, segments) {
+        (Method::Options, _) => json_reply(204, Value::Null)
+            .with_header(header("Access-Control-Allow-Methods", "GET, POST, DELETE"))
+            .with_header(header("Access-Control-Allow-Headers", "Content-Type")),
+        (Method::Post, ["runs"]) => {
+            let submitted = read_body::<Option<RunRequest>>(request)
+                .and_then(|body| body.ok_or_else(|| anyhow!("A run needs a JSON body")))
+                .and_then(|body| submit(runs, body));
+            match submitted {
+                Ok(body) => json_reply(201, body),
+                Err(e) => error_reply(400, e),
+            }
+        }
+        (Method::Get, ["runs"]) => {
+            let clients: Vec<RunClient> =
+                lock(runs).runs.values().map(|run| run.client()).collect();
+            let statuses: Vec<Value> = clients
+                .iter()
+                .filter_map(|client| client.request(RunCommand::Status))
+                .collect();
+            json_reply(200, Value::Array(statuses))
+        }
+        (_, ["runs", id, rest @ ..]) => {
+            let Some((id, client)) = id
+                .parse::<u32>()
+                .ok()
+                .and_then(|id| lock(runs).client(id).map(|client| (id, client)))
+            else {
+                return error_reply(404, format!("No run {}", id));
+            };
+            let command = match (method, rest) {
+                (Method::Get, []) => RunCommand::Status,
+                (Method::Delete, []) => {
+                    //Dropped outside the lock, since it waits for the worker to stop
+                    let run = lock(runs).runs.remove(&id);
+                    drop(run);
+                    return json_reply(200, json!({ "id": id, "deleted": true }));
+                }
+                (Method::Post, ["start"]) => match read_body::<ControlRequest>(request) {
+                    Ok(body) => RunCommand::Start(body.interval_ms.unwrap_or(0)),
+                    Err(e) => return error_reply(400, e),
+                },
+                (Method::Post, ["pause"]) => RunCommand::Pause,
+                (Method::Post, ["step"]) => match read_body::<ControlRequest>(request) {
+                    Ok(body) => RunCommand::Step(
+                        body.steps.or_else(|| query_value(query, "n")).unwrap_or(1),
+                    ),
+                    Err(e) => return error_reply(400, e),
+                },
+                (Method::Get, ["agents"]) => RunCommand::Agents,
+                (Method::Get, ["metrics"]) => RunCommand::Metrics,
+                (Method::Get, ["outputs"]) => {
+                    return match lock(runs).outputs(id) {
+                        Some(files) => json_reply(200, json!({ "id": id, "files": files })),
+                        None => error_reply(404, format!("No run {}", id)),
+                    };
+                }
+                (Method::Get, ["outputs", file]) => {
+                    return error_reply(404, format!("Run {} has no output {}", id, file))
+                }
+                _ => return error_reply(404, "Unknown route"),
+            };
+            match client.request(command) {
+                Some(body) => json_reply(200, body),
+                None => error_reply(500, format!("Run {} has stopped", id)),
+            }
+        }
+        _ => error_reply(404, "Unknown route"),
+    }
+}
+
+/// Answer API requests on `address` until the process ends. Each run writes its outputs
+/// to `run_{id}` in `output_dir`.
+pub fn serve(address: &str, output_dir: &str) -> Result<(), Error> {
+    let server =
+        Server::http(address).map_err(|e| anyhow!("Could not listen on {}: {}", address, e))?;
+    println!("Serving the simulation API on http://{}", address);
+
+    let runs = Arc::new(Mutex::new(Runs {
+        output_dir: output_dir.to_string(),
+        next_id: 0,
+        runs: BTreeMap::new(),
+    }));
+    //A long step request only holds up its own client
+    for request in server.incoming_requests() {
+        let runs = Arc::clone(&runs);
+        thread::spawn(move || handle(&runs, request));
+    }
+    Ok(())
+}
@@ -0,0 +1,232 @@
+use crate::model::{
+    scenario::Scenario, state::state::ModelState, trip::TripRecord, trip::TripSummary,
+};
+use crate::simulation::Simulation;
+
+use ndarray::Array2;
+use serde_json::{json, Value};
+use std::sync::mpsc::{self, Receiver, Sender, TryRecvError};
+use std::thread::{self, JoinHandle};
+use std::time::Duration;
+
+pub enum RunCommand {
+    Status,
+    //Step continuously, waiting the given milliseconds between steps
+    Start(u64),
+    Pause,
+    Step(u64),
+    Agents,
+    Metrics,
+    Stop,
+}
+
+/// Settings of a run, checked before its worker thread starts
+pub struct RunSetup {
+    pub dim: (f32, f32),
+    pub grid: Option<Array2<u8>>,
+    pub num_agents: u32,
+    pub num_steps: u64,
+    pub scenario: Scenario,
+}
+
+/// Sends commands to a run's worker, from any thread
+#[derive(Clone)]
+pub struct RunClient {
+    commands: Sender<(RunCommand, Sender<Value>)>,
+}
+
+impl RunClient {
+    /// Send a command and wait for the worker's answer, or None if the worker has gone
+    pub fn request(&self, command: RunCommand) -> Option<Value> {
+        let (reply, answer) = mpsc::channel();
+        self.commands.send((command, reply)).ok()?;
+        answer.recv().ok()
+    }
+}
+
+/// A run owned by its own worker thread, which answers commands with JSON
+pub struct RunHandle {
+    pub output_dir: String,
+    client: RunClient,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl RunHandle {
+    pub fn spawn(id: u32, setup: RunSetup) -> RunHandle {
+        let output_dir = setup.scenario.output_dir.clone();
+        let (commands, receiver) = mpsc::channel();
+        //The state never leaves the thread, since the schedule's agents are not Send
+        let thread = thread::spawn(move || {
+            let state = ModelState::new(
+                setup.dim,
+                setup.num_agents,
+                setup.num_steps,
+                setup.grid,
+                setup.scenario,
+            );
+            RunWorker {
+                id,
+                simulation: Simulation::new(state),
+                interval: None,
+                stepping: None,
+            }
+            .serve(receiver);
+        });
+        RunHandle {
+            output_dir,
+            client: RunClient { commands },
+            thread: Some(thread),
+        }
+    }
+
+    pub fn client(&self) -> RunClient {
+        self.client.clone()
+    }
+}
+
+impl Drop for RunHandle {
+    fn drop(&mut self) {
+        self.client.request(RunCommand::Stop);
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+struct RunWorker {
+    id: u32,
+    simulation: Simulation,
+    //Milliseconds between steps while running, None while paused
+    interval: Option<u64>,
+    //Steps left of a step request, and where to answer it once they are taken
+    stepping: Option<(u64, Sender<Value>)>,
+}
+
+impl RunWorker {
+    fn serve(mut self, commands: Receiver<(RunCommand, Sender<Value>)>) {
+        loop {
+            let running = self.interval.is_some() && !self.simulation.is_finished();
+            let message = if running || self.stepping.is_some() {
+                match commands.try_recv() {
+                    Ok(message) => Some(message),
+                    Err(TryRecvError::Empty) => None,
+                    Err(TryRecvError::Disconnected) => return,
+                }
+            } else {
+                match commands.recv() {
+                    Ok(message) => Some(message),
+                    Err(_) => return,
+                }
+            };
+
+            if let Some((command, reply)) = message {
+                let stop = matches!(command, RunCommand::Stop);
+                self.answer(command, reply);
+                if stop {
+                    return;
+                }
+                continue;
+            }
+
+            //Commands are answered between the steps of a step request
+            if let Some((steps_left, _)) = self.stepping.as_mut() {
+                *steps_left -= 1;
+                let done = *steps_left == 0;
+                if !self.simulation.step() || done {
+                    self.finish_stepping();
+                }
+                continue;
+            }
+
+            self.simulation.step();
+            if let Some(interval) = self.interval.filter(|interval| *interval > 0) {
+                thread::sleep(Duration::from_millis(interval));
+            }
+        }
+    }
+
+    fn answer(&mut self, command: RunCommand, reply: Sender<Value>) {
+        let answer = match command {
+            RunCommand::Status => self.status(),
+            RunCommand::Stop => {
+                self.finish_stepping();
+                self.status()
+            }
+            RunCommand::Start(interval) => {
+                self.finish_stepping();
+                self.interval = Some(interval);
+                self.status()
+            }
+            RunCommand::Pause => {
+                self.finish_stepping();
+                self.interval = None;
+                self.status()
+            }
+            RunCommand::Step(steps) => {
+                self.finish_stepping();
+                self.interval = None;
+                if steps > 0 && !self.simulation.is_finished() {
+                    //Answered with the status reached once the steps are taken
+                    self.stepping = Some((steps, reply));
+                    return;
+                }
+                self.status()
+            }
+            RunCommand::Agents => self.agents(),
+            RunCommand::Metrics => self.metrics(),
+        };
+        let _ = reply.send(answer);
+    }
+
+    //Answer a step request with the status reached, when its steps are taken or it is cut short
+    fn finish_stepping(&mut self) {
+        if let Some((_, reply)) = self.stepping.take() {
+            let _ = reply.send(self.status());
+        }
+    }
+
+    fn status(&self) -> Value {
+        let state = &self.simulation.state;
+        json!({
+            "id": self.id,
+            "step": state.step,
+            "num_steps": state.num_steps,
+            "running": self.interval.is_some() && !self.simulation.is_finished(),
+            "finished": self.simulation.is_finished(),
+        })
+    }
+
+    fn agents(&self) -> Value {
+        let state = &self.simulation.state;
+        let speed_factor = state.cell_size / state.step_duration;
+        let mut peds: Vec<_> = state.active_peds.values().collect();
+        peds.sort_by_key(|ped| ped.id);
+        let agents: Vec<Value> = peds
+            .iter()
+            .map(|ped| {
+                json!({
+                    "id": ped.id,
+                    "x": ped.loc.x * state.cell_size,
+                    "y": ped.loc.y * state.cell_size,
+                    "vx": ped.last_d.x * speed_factor,
+                    "vy": ped.last_d.y * speed_factor,
+                    "state": ped.status.to_string(),
+                })
+            })
+            .collect();
+        json!({ "step": state.step, "agents": agents })
+    }
+
+    fn metrics(&self) -> Value {
+        let state = &self.simulation.state;
+        let records: Vec<TripRecord> = state.trips.values().cloned().collect();
+        let summary = TripSummary::from_records(state.run, &records);
+        json!({
+            "step": state.step,
+            "time": state.step as f32 * state.step_duration,
+            "on_field": state.active_peds.len(),
+            "evacuated": state.evacuated.len(),
+            "trips": summary,
+        })
+    }
+}
@@ -0,0 +1,46 @@
+use crate::model::state::state::ModelState;
+
+use krabmaga::engine::{schedule::Schedule, state::State};
+
+/// A model state with its schedule, for stepping a single run from other code.
+/// `simulate!` does the same for whole series of repetitions.
+pub struct Simulation {
+    pub state: ModelState,
+    schedule: Schedule,
+    finished: bool,
+}
+
+impl Simulation {
+    /// Schedule the state's pedestrians, ready for the first step
+    pub fn new(mut state: ModelState) -> Simulation {
+        let mut schedule = Schedule::new();
+        state.init(&mut schedule);
+        Simulation {
+            state,
+            schedule,
+            finished: false,
+        }
+    }
+
+    /// Take one step, unless the run has already ended. Returns whether the run goes on.
+    pub fn step(&mut self) -> bool {
+        if !self.finished {
+            self.schedule.step(&mut self.state);
+            self.finished = self.state.end_condition(&mut self.schedule);
+        }
+        !self.finished
+    }
+
+    /// Step until the run ends, at `num_steps` or once an evacuation is complete
+    pub fn run(&mut self) {
+        while self.step() {}
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.finished
+    }
+
+    pub fn into_state(self) -> ModelState {
+        self.state
+    }
+}
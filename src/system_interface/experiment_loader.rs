@@ -0,0 +1,10 @@
+use crate::model::experiment::Experiment;
+use anyhow::Error;
+use std::fs::File;
+use std::io::BufReader;
+
+pub fn read_experiment(filepath: String) -> Result<Experiment, Error> {
+    let reader = BufReader::new(File::open(filepath)?);
+    let experiment: Experiment = serde_json::from_reader(reader)?;
+    Ok(experiment)
+}
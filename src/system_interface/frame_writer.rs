@@ -0,0 +1,57 @@
+use crate::system_interface::output_writer::output_path;
+use anyhow::Error;
+use image::codecs::gif::{GifEncoder, Repeat};
+use image::{Delay, DynamicImage, Frame, RgbImage};
+use std::fs::File;
+use std::io::BufWriter;
+
+/// Writes rendered frames as `<name>_<step>.png` and, with a GIF enabled, appends them
+/// to the animation `<name>.gif` as they come in. The GIF is completed when the writer is dropped.
+pub struct FrameWriter {
+    output_dir: String,
+    name: String,
+    png: bool,
+    gif: Option<GifEncoder<BufWriter<File>>>,
+    frame_delay: Delay,
+}
+
+impl FrameWriter {
+    pub fn new(
+        output_dir: &str,
+        name: &str,
+        png: bool,
+        gif: bool,
+        frame_delay_ms: u32,
+    ) -> Result<FrameWriter, Error> {
+        let gif = if gif {
+            let file = File::create(output_path(output_dir, &format!("{}.gif", name))?)?;
+            let mut encoder = GifEncoder::new(BufWriter::new(file));
+            encoder.set_repeat(Repeat::Infinite)?;
+            Some(encoder)
+        } else {
+            None
+        };
+
+        Ok(FrameWriter {
+            output_dir: output_dir.to_string(),
+            name: name.to_string(),
+            png,
+            gif,
+            frame_delay: Delay::from_numer_denom_ms(frame_delay_ms, 1),
+        })
+    }
+
+    pub fn write(&mut self, step: u64, frame: RgbImage) -> Result<(), Error> {
+        if self.png {
+            frame.save(output_path(
+                &self.output_dir,
+                &format!("{}_{:06}.png", self.name, step),
+            )?)?;
+        }
+        if let Some(encoder) = self.gif.as_mut() {
+            let rgba = DynamicImage::ImageRgb8(frame).into_rgba8();
+            encoder.encode_frame(Frame::from_parts(rgba, 0, 0, self.frame_delay))?;
+        }
+        Ok(())
+    }
+}
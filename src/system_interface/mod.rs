@@ -1 +1,10 @@
+pub mod experiment_loader;
+pub mod frame_writer;
 pub mod object_grid_loader;
+pub mod output_writer;
+pub mod plot_writer;
+pub mod raster_writer;
+pub mod scenario_loader;
+pub mod snapshot_io;
+pub mod stream_server;
+pub mod trajectory_writer;
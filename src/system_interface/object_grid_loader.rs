@@ -33,3 +33,16 @@ pub fn read_raster(filepath: String) -> Result<Array2<u8>, ImageError> {
         }
     }
 }
+
+/// Grid dimensions (columns, rows) and the obstacle raster, if any
+pub type ObstacleGrid = ((f32, f32), Option<Array2<u8>>);
+
+/// Grid dimensions and obstacle raster read from `input`, or an open 400 x 400 grid
+/// without a raster when `input` is empty
+pub fn load_obstacle_grid(input: &str) -> Result<ObstacleGrid, ImageError> {
+    if input.is_empty() {
+        return Ok(((400., 400.), None));
+    }
+    let grid = read_raster(input.to_string())?;
+    Ok(((grid.ncols() as f32, grid.nrows() as f32), Some(grid)))
+}
@@ -0,0 +1,21 @@
+use std::fs::{self, File};
+use std::io::{BufWriter, Write};
+use std::path::{Path, PathBuf};
+
+//Path of a run output file, creating the output directory if needed
+pub fn output_path(output_dir: &str, file_name: &str) -> std::io::Result<PathBuf> {
+    fs::create_dir_all(output_dir)?;
+    Ok(Path::new(output_dir).join(file_name))
+}
+
+pub fn write_csv<I>(path: &Path, header: &str, rows: I) -> std::io::Result<()>
+where
+    I: IntoIterator<Item = String>,
+{
+    let mut writer = BufWriter::new(File::create(path)?);
+    writeln!(writer, "{}", header)?;
+    for row in rows {
+        writeln!(writer, "{}", row)?;
+    }
+    writer.flush()
+}
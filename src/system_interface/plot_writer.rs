@@ -0,0 +1,120 @@
+use image::{Rgb, RgbImage};
+
+const PLOT_WIDTH: u32 = 480;
+const PLOT_HEIGHT: u32 = 360;
+const MARGIN: u32 = 24;
+
+const BACKGROUND: Rgb<u8> = Rgb([255, 255, 255]);
+const GRID: Rgb<u8> = Rgb([225, 225, 225]);
+const AXIS: Rgb<u8> = Rgb([0, 0, 0]);
+
+/// Colours for successive series of a plot
+pub const SERIES_COLOURS: [Rgb<u8>; 4] = [
+    Rgb([31, 119, 180]),
+    Rgb([255, 127, 14]),
+    Rgb([44, 160, 44]),
+    Rgb([214, 39, 40]),
+];
+
+/// Value range and grid line spacing of both axes; both axes start at 0
+#[derive(Copy, Clone, Debug)]
+pub struct PlotAxes {
+    pub x_max: f32,
+    pub y_max: f32,
+    pub x_tick: f32,
+    pub y_tick: f32,
+}
+
+pub struct Series {
+    pub points: Vec<(f32, f32)>,
+    pub colour: Rgb<u8>,
+}
+
+//Pixel of a value pair, or None when it falls outside the axes
+fn to_pixel(axes: &PlotAxes, x: f32, y: f32) -> Option<(u32, u32)> {
+    if !(0. ..=axes.x_max).contains(&x) || !(0. ..=axes.y_max).contains(&y) {
+        return None;
+    }
+    let inner_width = (PLOT_WIDTH - 2 * MARGIN) as f32;
+    let inner_height = (PLOT_HEIGHT - 2 * MARGIN) as f32;
+    let col = MARGIN + (x / axes.x_max * inner_width) as u32;
+    let row = PLOT_HEIGHT - MARGIN - (y / axes.y_max * inner_height) as u32;
+    Some((col, row))
+}
+
+/// Scatter plot of the series over grid lines, with an optional reference curve drawn in black.
+/// There is no text: the axis ranges and the meaning of the colours are left to the caller.
+pub fn scatter_plot(
+    axes: &PlotAxes,
+    series: &[Series],
+    curve: Option<&dyn Fn(f32) -> f32>,
+) -> RgbImage {
+    let mut image = RgbImage::from_pixel(PLOT_WIDTH, PLOT_HEIGHT, BACKGROUND);
+    let (left, right) = (MARGIN, PLOT_WIDTH - MARGIN);
+    let (top, bottom) = (MARGIN, PLOT_HEIGHT - MARGIN);
+
+    let mut x = axes.x_tick;
+    while axes.x_tick > 0. && x <= axes.x_max {
+        if let Some((col, _)) = to_pixel(axes, x, 0.) {
+            (top..=bottom).for_each(|row| image.put_pixel(col, row, GRID));
+        }
+        x += axes.x_tick;
+    }
+    let mut y = axes.y_tick;
+    while axes.y_tick > 0. && y <= axes.y_max {
+        if let Some((_, row)) = to_pixel(axes, 0., y) {
+            (left..=right).for_each(|col| image.put_pixel(col, row, GRID));
+        }
+        y += axes.y_tick;
+    }
+    (left..=right).for_each(|col| image.put_pixel(col, bottom, AXIS));
+    (top..=bottom).for_each(|row| image.put_pixel(left, row, AXIS));
+
+    for s in series {
+        for (x, y) in s.points.iter() {
+            let Some((col, row)) = to_pixel(axes, *x, *y) else {
+                continue;
+            };
+            for (dc, dr) in itertools::iproduct!(0..3, 0..3) {
+                let (c, r) = ((col + dc).saturating_sub(1), (row + dr).saturating_sub(1));
+                if c < PLOT_WIDTH && r < PLOT_HEIGHT {
+                    image.put_pixel(c, r, s.colour);
+                }
+            }
+        }
+    }
+
+    if let Some(curve) = curve {
+        //Join the curve's values at neighbouring pixel columns with vertical runs
+        let mut previous: Option<u32> = None;
+        for col in left..=right {
+            let x = (col - left) as f32 / (right - left) as f32 * axes.x_max;
+            let y = curve(x).clamp(0., axes.y_max);
+            let Some((_, row)) = to_pixel(axes, x, y) else {
+                continue;
+            };
+            let (from, to) = match previous {
+                Some(prev) => (prev.min(row), prev.max(row)),
+                None => (row, row),
+            };
+            (from..=to).for_each(|r| image.put_pixel(col, r, AXIS));
+            previous = Some(row);
+        }
+    }
+    image
+}
+
+/// Place plots next to each other, top-aligned
+pub fn side_by_side(plots: &[RgbImage]) -> RgbImage {
+    let width = plots.iter().map(|plot| plot.width()).sum();
+    let height = plots.iter().map(|plot| plot.height()).max().unwrap_or(0);
+    let mut image = RgbImage::from_pixel(width, height, BACKGROUND);
+    let mut offset = 0;
+    for plot in plots {
+        for (col, row, pixel) in plot.enumerate_pixels() {
+            image.put_pixel(offset + col, row, *pixel);
+        }
+        offset += plot.width();
+    }
+    image
+}
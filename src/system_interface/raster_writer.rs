@@ -0,0 +1,83 @@
+use anyhow::Error;
+use image::{Rgb, RgbImage};
+use ndarray::Array2;
+use std::fs::File;
+use std::path::Path;
+use tiff::encoder::{colortype, TiffEncoder};
+use tiff::tags::Tag;
+
+//GeoKey directory: version 1.1.0 with 4 keys, each as key id, location (0: value inline),
+//count and value. A projected, user-defined coordinate system in metres, pixels as areas.
+const GEO_KEYS: [u16; 20] = [
+    1, 1, 0, 4, //Version and number of keys
+    1024, 0, 1, 1, //GTModelTypeGeoKey: projected
+    1025, 0, 1, 1, //GTRasterTypeGeoKey: pixel is area
+    3072, 0, 1, 32767, //ProjectedCSTypeGeoKey: user-defined
+    3076, 0, 1, 9001, //ProjLinearUnitsGeoKey: metre
+];
+
+//Colour ramp from pale yellow through orange to dark red, for values in [0, 1]
+const COLOUR_RAMP: [[f32; 3]; 4] = [
+    [255., 255., 204.],
+    [254., 178., 76.],
+    [240., 59., 32.],
+    [128., 0., 38.],
+];
+
+pub fn ramp_colour(value: f32) -> Rgb<u8> {
+    let scaled = value.clamp(0., 1.) * (COLOUR_RAMP.len() - 1) as f32;
+    let lower = (scaled.floor() as usize).min(COLOUR_RAMP.len() - 2);
+    let t = scaled - lower as f32;
+    let (a, b) = (COLOUR_RAMP[lower], COLOUR_RAMP[lower + 1]);
+    Rgb([
+        (a[0] + (b[0] - a[0]) * t) as u8,
+        (a[1] + (b[1] - a[1]) * t) as u8,
+        (a[2] + (b[2] - a[2]) * t) as u8,
+    ])
+}
+
+//Colour a raster of values [[row, col]] from 0 to max_value on the ramp.
+//Cells where navigable is 0 are drawn black, as in the obstacle raster.
+pub fn heatmap_image(values: &Array2<f32>, max_value: f32, navigable: &Array2<u8>) -> RgbImage {
+    let (height, width) = values.dim();
+    RgbImage::from_fn(width as u32, height as u32, |col, row| {
+        let idx = [row as usize, col as usize];
+        match navigable.get(idx) {
+            Some(&0) => Rgb([0, 0, 0]),
+            _ => ramp_colour(values[idx] / max_value),
+        }
+    })
+}
+
+pub fn write_png_heatmap(
+    path: &Path,
+    values: &Array2<f32>,
+    max_value: f32,
+    navigable: &Array2<u8>,
+) -> Result<(), Error> {
+    heatmap_image(values, max_value, navigable).save(path)?;
+    Ok(())
+}
+
+//Single-band 32-bit float GeoTIFF with square pixels of cell_size metres and the
+//origin at the top left corner of the obstacle raster. The y scale is negative, so model
+//y grows with the rows, in the simulation's own metres.
+pub fn write_geotiff(path: &Path, values: &Array2<f32>, cell_size: f32) -> Result<(), Error> {
+    let (height, width) = values.dim();
+    let data: Vec<f32> = values.iter().copied().collect();
+
+    let mut encoder = TiffEncoder::new(File::create(path)?)?;
+    let mut image = encoder.new_image::<colortype::Gray32Float>(width as u32, height as u32)?;
+    image.encoder().write_tag(
+        Tag::ModelPixelScaleTag,
+        &[cell_size as f64, -cell_size as f64, 0.][..],
+    )?;
+    image
+        .encoder()
+        .write_tag(Tag::ModelTiepointTag, &[0., 0., 0., 0., 0., 0.][..])?;
+    image
+        .encoder()
+        .write_tag(Tag::GeoKeyDirectoryTag, &GEO_KEYS[..])?;
+    image.write_data(&data)?;
+    Ok(())
+}
@@ -0,0 +1,10 @@
+use crate::model::scenario::Scenario;
+use anyhow::Error;
+use std::fs::File;
+use std::io::BufReader;
+
+pub fn read_scenario(filepath: String) -> Result<Scenario, Error> {
+    let reader = BufReader::new(File::open(filepath)?);
+    let scenario: Scenario = serde_json::from_reader(reader)?;
+    Ok(scenario)
+}
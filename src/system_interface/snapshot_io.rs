@@ -0,0 +1,18 @@
+use crate::model::snapshot::Snapshot;
+use anyhow::Error;
+use std::fs::File;
+use std::io::{BufReader, BufWriter, Write};
+use std::path::Path;
+
+pub fn read_snapshot(filepath: String) -> Result<Snapshot, Error> {
+    let reader = BufReader::new(File::open(filepath)?);
+    let snapshot: Snapshot = serde_json::from_reader(reader)?;
+    Ok(snapshot)
+}
+
+pub fn write_snapshot(path: &Path, snapshot: &Snapshot) -> Result<(), Error> {
+    let mut writer = BufWriter::new(File::create(path)?);
+    serde_json::to_writer(&mut writer, snapshot)?;
+    writer.flush()?;
+    Ok(())
+}
@@ -0,0 +1,63 @@
+use anyhow::Error;
+use std::net::{TcpListener, TcpStream};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Duration;
+use tungstenite::{accept, Message, WebSocket};
+
+//Viewers that stop reading are dropped rather than holding up the simulation
+const WRITE_TIMEOUT: Duration = Duration::from_millis(500);
+//Connections that never finish the WebSocket handshake are given up on
+const HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// WebSocket endpoint that forwards every broadcast message to all connected viewers.
+/// Connections are accepted on a background thread for as long as the process runs.
+pub struct StreamServer {
+    clients: Arc<Mutex<Vec<WebSocket<TcpStream>>>>,
+}
+
+impl StreamServer {
+    pub fn bind(address: &str) -> Result<StreamServer, Error> {
+        let listener = TcpListener::bind(address)?;
+        let clients = Arc::new(Mutex::new(Vec::new()));
+
+        let accepted = Arc::clone(&clients);
+        thread::spawn(move || {
+            for stream in listener.incoming().filter_map(|stream| stream.ok()) {
+                if stream.set_write_timeout(Some(WRITE_TIMEOUT)).is_err()
+                    || stream.set_read_timeout(Some(HANDSHAKE_TIMEOUT)).is_err()
+                {
+                    continue;
+                }
+                //Each handshake runs on its own thread, so a slow viewer cannot hold up the others
+                let accepted = Arc::clone(&accepted);
+                thread::spawn(move || match accept(stream) {
+                    Ok(socket) => accepted
+                        .lock()
+                        .expect("A broadcast panicked while holding the viewers")
+                        .push(socket),
+                    Err(e) => println!("Failed to accept stream viewer: {}", e),
+                });
+            }
+        });
+
+        println!("Streaming pedestrians on ws://{}", address);
+        Ok(StreamServer { clients })
+    }
+
+    pub fn has_clients(&self) -> bool {
+        !self
+            .clients
+            .lock()
+            .expect("A broadcast panicked while holding the viewers")
+            .is_empty()
+    }
+
+    /// Send a message to every viewer, forgetting those that have disconnected
+    pub fn broadcast(&self, message: Message) {
+        self.clients
+            .lock()
+            .expect("A broadcast panicked while holding the viewers")
+            .retain_mut(|client| client.send(message.clone()).is_ok());
+    }
+}
@@ -0,0 +1,165 @@
+use crate::model::pedestrian::PedStatus;
+use crate::system_interface::output_writer::output_path;
+use anyhow::Error;
+use std::fs::File;
+use std::io::{BufWriter, Write};
+
+pub const TRAJECTORY_HEADER: &str = "step,time,id,x,y,vx,vy,level,state";
+
+/// One pedestrian sample, with positions in metres and velocities in metres per second
+#[derive(Clone, Debug)]
+pub struct TrajectoryRow {
+    pub step: u64,
+    pub time: f32,
+    pub id: u32,
+    pub x: f32,
+    pub y: f32,
+    pub vx: f32,
+    pub vy: f32,
+    pub level: i32,
+    pub state: PedStatus,
+}
+
+/// Samples written to Parquet as one row group
+pub const PARQUET_ROW_GROUP_ROWS: usize = 65_536;
+
+/// Streams trajectory samples to `<name>.csv` as they are recorded. With Parquet enabled,
+/// they also go to `<name>.parquet`, a row group every `PARQUET_ROW_GROUP_ROWS` samples.
+pub struct TrajectoryWriter {
+    csv: BufWriter<File>,
+    parquet: Option<(ParquetSink, Vec<TrajectoryRow>)>,
+}
+
+impl TrajectoryWriter {
+    pub fn new(output_dir: &str, name: &str, parquet: bool) -> Result<TrajectoryWriter, Error> {
+        let parquet = match parquet {
+            true => {
+                let path = output_path(output_dir, &format!("{}.parquet", name))?;
+                Some((ParquetSink::create(&path)?, Vec::new()))
+            }
+            false => None,
+        };
+        let mut csv = BufWriter::new(File::create(output_path(
+            output_dir,
+            &format!("{}.csv", name),
+        )?)?);
+        writeln!(csv, "{}", TRAJECTORY_HEADER)?;
+
+        Ok(TrajectoryWriter { csv, parquet })
+    }
+
+    pub fn write(&mut self, rows: &[TrajectoryRow]) -> Result<(), Error> {
+        for row in rows {
+            writeln!(
+                self.csv,
+                "{},{},{},{},{},{},{},{},{}",
+                row.step, row.time, row.id, row.x, row.y, row.vx, row.vy, row.level, row.state
+            )?;
+        }
+        if let Some((sink, pending)) = self.parquet.as_mut() {
+            pending.extend_from_slice(rows);
+            if pending.len() >= PARQUET_ROW_GROUP_ROWS {
+                sink.write_row_group(pending)?;
+                pending.clear();
+            }
+        }
+        Ok(())
+    }
+
+    pub fn finish(mut self) -> Result<(), Error> {
+        self.csv.flush()?;
+        if let Some((mut sink, pending)) = self.parquet.take() {
+            if !pending.is_empty() {
+                sink.write_row_group(&pending)?;
+            }
+            sink.close()?;
+        }
+        Ok(())
+    }
+}
+
+/// Parquet file the trajectory row groups are appended to
+#[cfg(feature = "parquet")]
+struct ParquetSink {
+    writer: parquet::arrow::ArrowWriter<File>,
+    schema: arrow::datatypes::SchemaRef,
+}
+
+#[cfg(feature = "parquet")]
+impl ParquetSink {
+    fn create(path: &std::path::Path) -> Result<ParquetSink, Error> {
+        use arrow::datatypes::{DataType, Field, Schema};
+        use parquet::arrow::ArrowWriter;
+        use std::sync::Arc;
+
+        let schema = Arc::new(Schema::new(vec![
+            Field::new("step", DataType::UInt64, false),
+            Field::new("time", DataType::Float32, false),
+            Field::new("id", DataType::UInt32, false),
+            Field::new("x", DataType::Float32, false),
+            Field::new("y", DataType::Float32, false),
+            Field::new("vx", DataType::Float32, false),
+            Field::new("vy", DataType::Float32, false),
+            Field::new("level", DataType::Int32, false),
+            Field::new("state", DataType::Utf8, false),
+        ]));
+        let writer = ArrowWriter::try_new(File::create(path)?, schema.clone(), None)?;
+        Ok(ParquetSink { writer, schema })
+    }
+
+    fn write_row_group(&mut self, rows: &[TrajectoryRow]) -> Result<(), Error> {
+        use arrow::array::{
+            ArrayRef, Float32Array, Int32Array, StringArray, UInt32Array, UInt64Array,
+        };
+        use arrow::record_batch::RecordBatch;
+        use std::sync::Arc;
+
+        let columns: Vec<ArrayRef> = vec![
+            Arc::new(UInt64Array::from_iter_values(rows.iter().map(|r| r.step))),
+            Arc::new(Float32Array::from_iter_values(rows.iter().map(|r| r.time))),
+            Arc::new(UInt32Array::from_iter_values(rows.iter().map(|r| r.id))),
+            Arc::new(Float32Array::from_iter_values(rows.iter().map(|r| r.x))),
+            Arc::new(Float32Array::from_iter_values(rows.iter().map(|r| r.y))),
+            Arc::new(Float32Array::from_iter_values(rows.iter().map(|r| r.vx))),
+            Arc::new(Float32Array::from_iter_values(rows.iter().map(|r| r.vy))),
+            Arc::new(Int32Array::from_iter_values(rows.iter().map(|r| r.level))),
+            Arc::new(StringArray::from_iter_values(
+                rows.iter().map(|r| r.state.to_string()),
+            )),
+        ];
+        self.writer
+            .write(&RecordBatch::try_new(self.schema.clone(), columns)?)?;
+        //Ends the row group here, so the writer holds no more than one batch at a time
+        self.writer.flush()?;
+        Ok(())
+    }
+
+    fn close(self) -> Result<(), Error> {
+        self.writer.close()?;
+        Ok(())
+    }
+}
+
+fn parquet_unavailable() -> Error {
+    anyhow::anyhow!("Parquet output needs the crate to be built with the `parquet` feature")
+}
+
+//Without the feature a sink can never be created, so the trajectory output is refused when
+//it is opened rather than after a whole run
+#[cfg(not(feature = "parquet"))]
+struct ParquetSink(std::convert::Infallible);
+
+#[cfg(not(feature = "parquet"))]
+impl ParquetSink {
+    fn create(_path: &std::path::Path) -> Result<ParquetSink, Error> {
+        Err(parquet_unavailable())
+    }
+
+    fn write_row_group(&mut self, _rows: &[TrajectoryRow]) -> Result<(), Error> {
+        match self.0 {}
+    }
+
+    fn close(self) -> Result<(), Error> {
+        match self.0 {}
+    }
+}
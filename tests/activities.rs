@@ -0,0 +1,44 @@
+use ndarray::Array2;
+use pedestrian_sim::{ModelState, Scenario, Simulation};
+
+#[test]
+fn legs_between_stops_keep_out_of_walls() {
+    let scenario: Scenario = serde_json::from_str(&format!(
+        r#"{{
+            "output_dir": "{}",
+            "seed": 4,
+            "activity_chains": [{{"name": "both sides", "share": 1, "stops": [
+                {{"region": {{"x_min": 2, "y_min": 12, "x_max": 5, "y_max": 18}}, "dwell": {{"mean": 1, "sd": 0}}}},
+                {{"region": {{"x_min": 24, "y_min": 12, "x_max": 27, "y_max": 18}}, "dwell": {{"mean": 1, "sd": 0}}}}
+            ]}}]
+        }}"#,
+        std::env::temp_dir()
+            .join("pedestrian_sim_activities")
+            .to_string_lossy()
+            .replace('\\', "/")
+    ))
+    .unwrap();
+    //Walled down column 15, open only in the top and bottom three rows
+    let raster = Array2::from_shape_fn((30, 30), |(row, col)| {
+        match col == 15 && (3..27).contains(&row) {
+            true => 0,
+            false => 255,
+        }
+    });
+    let state = ModelState::new((30., 30.), 20, 400, Some(raster.clone()), scenario);
+
+    let mut simulation = Simulation::new(state);
+    while simulation.step() {
+        for ped in simulation.state.active_peds.values() {
+            assert_ne!(
+                raster[[ped.loc.y as usize, ped.loc.x as usize]],
+                0,
+                "pedestrian {} is inside the wall at step {}",
+                ped.id,
+                simulation.state.step
+            );
+        }
+    }
+    let state = simulation.into_state();
+    assert!(state.trips.values().any(|trip| trip.stops == 2));
+}
@@ -0,0 +1,41 @@
+use ndarray::Array2;
+use pedestrian_sim::{ModelState, Scenario, Simulation};
+
+#[test]
+fn rerouted_pedestrians_keep_out_of_walls() {
+    let scenario: Scenario = serde_json::from_str(&format!(
+        r#"{{
+            "output_dir": "{}",
+            "seed": 12,
+            "congestion": {{"interval": 2, "delay_threshold": 0, "density_weight": 5}}
+        }}"#,
+        std::env::temp_dir()
+            .join("pedestrian_sim_congestion")
+            .to_string_lossy()
+            .replace('\\', "/")
+    ))
+    .unwrap();
+    //Walled down column 15, open only in the top and bottom three rows
+    let raster = Array2::from_shape_fn((30, 30), |(row, col)| {
+        match col == 15 && (3..27).contains(&row) {
+            true => 0,
+            false => 255,
+        }
+    });
+    let state = ModelState::new((30., 30.), 60, 300, Some(raster.clone()), scenario);
+
+    let mut simulation = Simulation::new(state);
+    while simulation.step() {
+        for ped in simulation.state.active_peds.values() {
+            assert_ne!(
+                raster[[ped.loc.y as usize, ped.loc.x as usize]],
+                0,
+                "pedestrian {} is inside the wall at step {}",
+                ped.id,
+                simulation.state.step
+            );
+        }
+    }
+    let state = simulation.into_state();
+    assert!(state.trips.values().any(|trip| trip.replans > 0));
+}
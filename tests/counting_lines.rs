@@ -0,0 +1,16 @@
+use pedestrian_sim::{ModelState, Scenario};
+
+#[test]
+fn zero_length_lines_are_rejected() {
+    let scenario: Scenario = serde_json::from_str(
+        r#"{
+            "counting_lines": [{"name": "point", "start": [4.5, 4.5], "end": [4.5, 4.5]},
+                               {"name": "gate", "start": [4.5, 0], "end": [4.5, 9]}]
+        }"#,
+    )
+    .unwrap();
+    let state = ModelState::new((10., 10.), 0, 100, None, scenario);
+
+    assert_eq!(state.counting_lines.len(), 1);
+    assert_eq!(state.counting_lines[0].name, "gate");
+}
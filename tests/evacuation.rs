@@ -0,0 +1,76 @@
+use ndarray::Array2;
+use pedestrian_sim::{ModelState, Scenario, Simulation};
+
+#[test]
+fn pedestrians_behind_a_one_way_door_stay_inside() {
+    let scenario: Scenario = serde_json::from_str(&format!(
+        r#"{{
+            "output_dir": "{}",
+            "seed": 11,
+            "doors": [{{"region": {{"x_min": 5, "y_min": 0, "x_max": 5, "y_max": 9}},
+                        "flow_rate": 10, "direction": "-x"}}],
+            "evacuation": {{"exits": [{{"region": {{"x_min": 9, "y_min": 0, "x_max": 9, "y_max": 9}}}}]}}
+        }}"#,
+        std::env::temp_dir()
+            .join("pedestrian_sim_evacuation")
+            .to_string_lossy()
+            .replace('\\', "/")
+    ))
+    .unwrap();
+    let state = ModelState::new((10., 10.), 20, 100, None, scenario);
+    let trapped = state.peds.iter().filter(|ped| ped.loc.x <= 5.).count();
+    assert!(trapped > 0 && trapped < state.peds.len());
+
+    let mut simulation = Simulation::new(state);
+    simulation.run();
+    let state = simulation.into_state();
+
+    assert!(!state.evacuation_complete());
+    assert_eq!(state.step, 100);
+    assert_eq!(state.evacuated.len(), state.peds.len() - trapped);
+    assert_eq!(state.evacuation_curve.last(), Some(&(100, trapped)));
+}
+
+#[test]
+fn evacuees_rerouted_during_the_run_keep_out_of_walls() {
+    let scenario: Scenario = serde_json::from_str(&format!(
+        r#"{{
+            "output_dir": "{}",
+            "seed": 6,
+            "obstacle_events": [{{"region": {{"x_min": 15, "y_min": 0, "x_max": 15, "y_max": 2}},
+                                  "start_step": 5}}],
+            "evacuation": {{"exits": [{{"region": {{"x_min": 0, "y_min": 0, "x_max": 0, "y_max": 29}}}},
+                                      {{"region": {{"x_min": 29, "y_min": 0, "x_max": 29, "y_max": 29}}}}],
+                           "update_interval": 5, "congestion_radius": 20, "congestion_weight": 5}}
+        }}"#,
+        std::env::temp_dir()
+            .join("pedestrian_sim_evacuation_walls")
+            .to_string_lossy()
+            .replace('\\', "/")
+    ))
+    .unwrap();
+    //Walled down column 15, open only in the top and bottom three rows
+    let raster = Array2::from_shape_fn((30, 30), |(row, col)| {
+        match col == 15 && (3..27).contains(&row) {
+            true => 0,
+            false => 255,
+        }
+    });
+    let state = ModelState::new((30., 30.), 40, 300, Some(raster.clone()), scenario);
+
+    let mut simulation = Simulation::new(state);
+    while simulation.step() {
+        for ped in simulation.state.active_peds.values() {
+            assert_ne!(
+                raster[[ped.loc.y as usize, ped.loc.x as usize]],
+                0,
+                "pedestrian {} is inside the wall at step {}",
+                ped.id,
+                simulation.state.step
+            );
+        }
+    }
+    let state = simulation.into_state();
+    assert!(state.trips.values().any(|trip| trip.replans > 0));
+    assert!(state.evacuation_complete());
+}
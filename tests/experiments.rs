@@ -0,0 +1,47 @@
+use pedestrian_sim::{run_experiment, Experiment};
+use std::fs;
+
+#[test]
+fn every_run_of_the_sweep_gets_a_row_in_order() {
+    let output_dir = std::env::temp_dir().join("pedestrian_sim_experiment");
+    let experiment: Experiment = serde_json::from_str(&format!(
+        r#"{{
+            "num_agents": 5,
+            "steps": 30,
+            "repetitions": 2,
+            "seed": 40,
+            "threads": 3,
+            "output_dir": "{}",
+            "parameters": [{{"name": "num_agents", "values": [5, 10]}}]
+        }}"#,
+        output_dir.to_string_lossy().replace('\\', "/")
+    ))
+    .unwrap();
+    run_experiment(&experiment).unwrap();
+
+    let results = fs::read_to_string(output_dir.join("experiment_results.csv")).unwrap();
+    let rows: Vec<Vec<&str>> = results
+        .lines()
+        .skip(1)
+        .map(|line| line.split(',').collect())
+        .collect();
+    assert_eq!(rows.len(), 4);
+    //Combinations in order, each with its repetitions and their seeds
+    let keys: Vec<(&str, &str, &str)> = rows.iter().map(|row| (row[0], row[1], row[2])).collect();
+    assert_eq!(
+        keys,
+        [
+            ("5", "0", "40"),
+            ("5", "1", "41"),
+            ("10", "0", "40"),
+            ("10", "1", "41")
+        ]
+    );
+    //Every run went to its end
+    assert!(rows.iter().all(|row| row[3] == "30"));
+    for (combination, repetition) in [(0, 0), (0, 1), (1, 0), (1, 1)] {
+        assert!(output_dir
+            .join(format!("run_{}_{}", combination, repetition))
+            .is_dir());
+    }
+}
@@ -0,0 +1,249 @@
+use krabmaga::engine::location::Real2D;
+use pedestrian_sim::{ModelState, Pedestrian, Scenario};
+
+fn state(scenario: &str) -> ModelState {
+    let scenario: Scenario = serde_json::from_str(scenario).unwrap();
+    ModelState::new((10., 10.), 0, 100, None, scenario)
+}
+
+const KERB: Real2D = Real2D { x: 4., y: 5. };
+const ON_CROSSING: Real2D = Real2D { x: 5., y: 5. };
+
+#[test]
+fn move_held_at_a_door_is_not_counted_onto_the_crossing() {
+    let mut state = state(
+        r#"{
+            "crossings": [{"region": {"x_min": 5, "y_min": 0, "x_max": 5, "y_max": 9},
+                           "signal": {"green": 10, "red": 10}}],
+            "doors": [{"region": {"x_min": 5, "y_min": 5, "x_max": 5, "y_max": 5},
+                       "flow_rate": 0}]
+        }"#,
+    );
+    state.refill_doors();
+
+    assert!(!state.may_advance(1, KERB, ON_CROSSING));
+    assert_eq!(state.crossing_stats[0].crossings, 0);
+    assert!(state.crossing_stats[0].wait_steps.is_empty());
+    assert_eq!(
+        state.door_states[0].queue.front().map(|(id, _)| *id),
+        Some(1)
+    );
+}
+
+#[test]
+fn move_held_at_a_door_does_not_draw_a_violation() {
+    let mut state = state(
+        r#"{
+            "seed": 3,
+            "crossings": [{"region": {"x_min": 5, "y_min": 0, "x_max": 5, "y_max": 9},
+                           "signal": {"green": 10, "red": 10, "offset": 10},
+                           "violation_probability": 0.5}],
+            "doors": [{"region": {"x_min": 5, "y_min": 5, "x_max": 5, "y_max": 5},
+                       "flow_rate": 0}]
+        }"#,
+    );
+    state.refill_doors();
+    let word_pos = state.rng.get_word_pos();
+
+    assert!(!state.may_advance(1, KERB, ON_CROSSING));
+    assert_eq!(state.rng.get_word_pos(), word_pos);
+    assert_eq!(state.crossing_stats[0].violations, 0);
+}
+
+#[test]
+fn open_door_lets_the_pedestrian_onto_a_green_crossing() {
+    let mut state = state(
+        r#"{
+            "crossings": [{"region": {"x_min": 5, "y_min": 0, "x_max": 5, "y_max": 9},
+                           "signal": {"green": 10, "red": 10}}],
+            "doors": [{"region": {"x_min": 5, "y_min": 5, "x_max": 5, "y_max": 5},
+                       "flow_rate": 1}]
+        }"#,
+    );
+    state.refill_doors();
+
+    assert!(state.may_advance(1, KERB, ON_CROSSING));
+    assert_eq!(state.crossing_stats[0].crossings, 1);
+    assert_eq!(state.door_states[0].passages, 1);
+    //The door had one passage this step
+    assert!(!state.may_advance(2, KERB, ON_CROSSING));
+    assert_eq!(state.crossing_stats[0].crossings, 1);
+}
+
+#[test]
+fn everyone_waiting_at_the_kerb_steps_on_together_at_green() {
+    //Red for the first ten steps, then green
+    let mut state = state(
+        r#"{
+            "crossings": [{"region": {"x_min": 5, "y_min": 0, "x_max": 5, "y_max": 9},
+                           "signal": {"green": 10, "red": 10, "offset": 10}}]
+        }"#,
+    );
+    for (id, step) in [(1, 2), (2, 5), (3, 8)] {
+        state.step = step;
+        assert!(!state.may_advance(id, KERB, ON_CROSSING));
+    }
+
+    //Those who came last are not held back behind the others
+    state.step = 10;
+    for id in [3, 1, 2] {
+        assert!(state.may_advance(id, KERB, ON_CROSSING));
+    }
+    assert_eq!(state.crossing_stats[0].crossings, 3);
+    assert_eq!(state.crossing_stats[0].wait_steps, [2, 8, 5]);
+    assert!(state.crossing_waits.is_empty());
+}
+
+const DOOR: &str = r#"{
+    "doors": [{"region": {"x_min": 5, "y_min": 5, "x_max": 5, "y_max": 5},
+               "flow_rate": 0}]
+}"#;
+
+fn door_queue(state: &ModelState) -> Vec<u32> {
+    state.door_states[0]
+        .queue
+        .iter()
+        .map(|(id, _)| *id)
+        .collect()
+}
+
+#[test]
+fn door_lets_its_queue_through_first_come_first_served() {
+    let mut state = state(DOOR);
+    state.refill_doors();
+    assert!(!state.may_advance(1, KERB, ON_CROSSING));
+    assert!(!state.may_advance(2, KERB, ON_CROSSING));
+    assert!(!state.may_advance(1, KERB, ON_CROSSING));
+    assert_eq!(door_queue(&state), [1, 2]);
+
+    //A single passage goes to the head of the queue, whoever tries first
+    state.door_states[0].tokens = 1.;
+    assert!(!state.may_advance(3, KERB, ON_CROSSING));
+    assert!(!state.may_advance(2, KERB, ON_CROSSING));
+    assert!(state.may_advance(1, KERB, ON_CROSSING));
+    assert_eq!(door_queue(&state), [2, 3]);
+
+    //Two passages go to the first two in the queue
+    state.door_states[0].tokens = 2.;
+    assert!(!state.may_advance(4, KERB, ON_CROSSING));
+    assert!(state.may_advance(3, KERB, ON_CROSSING));
+    assert!(state.may_advance(2, KERB, ON_CROSSING));
+    assert_eq!(door_queue(&state), [4]);
+    assert_eq!(state.door_states[0].max_queue, 3);
+}
+
+#[test]
+fn door_queue_drops_pedestrians_that_stopped_trying() {
+    let mut state = state(DOOR);
+    state.refill_doors();
+    assert!(!state.may_advance(1, KERB, ON_CROSSING));
+    assert!(!state.may_advance(2, KERB, ON_CROSSING));
+
+    //Only pedestrian 2 is still waiting at the door a step later
+    state.step = 1;
+    state.refill_doors();
+    assert!(!state.may_advance(2, KERB, ON_CROSSING));
+    state.step = 2;
+    state.refill_doors();
+    assert_eq!(door_queue(&state), [2]);
+
+    state.door_states[0].tokens = 1.;
+    assert!(state.may_advance(2, KERB, ON_CROSSING));
+}
+
+#[test]
+fn called_pedestrian_held_at_a_red_crossing_keeps_its_call() {
+    let mut state = state(
+        r#"{
+            "crossings": [{"region": {"x_min": 5, "y_min": 0, "x_max": 5, "y_max": 9},
+                           "signal": {"green": 10, "red": 10, "offset": 10}}],
+            "services": [{"region": {"x_min": 5, "y_min": 5, "x_max": 5, "y_max": 5},
+                          "service_time": {"mean": 5},
+                          "queue_start": [4, 5], "queue_direction": "-x"}]
+        }"#,
+    );
+    state.service_passes.insert(1, 5);
+
+    assert!(!state.may_advance(1, KERB, ON_CROSSING));
+    assert_eq!(state.service_passes.get(&1), Some(&5));
+    assert!(!state.dwelling.contains_key(&1));
+    assert!(state.service_states[0].queue.is_empty());
+}
+
+#[test]
+fn uncalled_pedestrian_joins_the_queue_without_crossing() {
+    let mut state = state(
+        r#"{
+            "crossings": [{"region": {"x_min": 5, "y_min": 0, "x_max": 5, "y_max": 9},
+                           "signal": {"green": 10, "red": 10}}],
+            "services": [{"region": {"x_min": 5, "y_min": 5, "x_max": 5, "y_max": 5},
+                          "service_time": {"mean": 5},
+                          "queue_start": [4, 5], "queue_direction": "-x"}]
+        }"#,
+    );
+
+    assert!(!state.may_advance(1, KERB, ON_CROSSING));
+    assert_eq!(state.service_states[0].queue.front(), Some(&1));
+    assert_eq!(state.crossing_stats[0].crossings, 0);
+}
+
+const QUEUE: &str = r#"{
+    "crossings": [{"region": {"x_min": 6, "y_min": 0, "x_max": 6, "y_max": 4},
+                   "signal": {"green": 10, "red": 10, "offset": 10}}],
+    "services": [{"region": {"x_min": 8, "y_min": 2, "x_max": 8, "y_max": 2},
+                  "service_time": {"mean": 5},
+                  "queue_start": [7, 2], "queue_direction": "-x"}]
+}"#;
+
+fn queueing(state: &mut ModelState, loc: Real2D) -> Pedestrian {
+    let ped = Pedestrian::new(
+        1,
+        loc,
+        Real2D { x: 0., y: 0. },
+        Some(Real2D { x: 9., y: 9. }),
+        1.,
+    );
+    state.service_states[0].queue.push_back(ped.id);
+    ped
+}
+
+#[test]
+fn moving_up_the_queue_waits_at_a_red_crossing() {
+    let mut state = state(QUEUE);
+    let ped = queueing(&mut state, Real2D { x: 5., y: 2. });
+
+    assert_eq!(state.queue_move(&ped), Some((ped.loc, true)));
+    assert!(state.crossing_waits.contains_key(&ped.id));
+}
+
+#[test]
+fn moving_up_the_queue_goes_around_the_service_point() {
+    let mut state = state(QUEUE);
+    let ped = queueing(&mut state, Real2D { x: 9., y: 2. });
+
+    let (next, standing) = state.queue_move(&ped).unwrap();
+    assert!(!standing);
+    assert_ne!((next.x, next.y), (8., 2.));
+}
+
+#[test]
+fn service_point_with_its_queue_off_the_grid_is_rejected() {
+    let scenario = serde_json::from_str(
+        r#"{
+            "services": [{"name": "off", "region": {"x_min": 0, "y_min": 2, "x_max": 0, "y_max": 2},
+                          "service_time": {"mean": 5},
+                          "queue_start": [-1, 2], "queue_direction": "-x"},
+                         {"name": "short", "region": {"x_min": 2, "y_min": 2, "x_max": 2, "y_max": 2},
+                          "service_time": {"mean": 5},
+                          "queue_start": [1, 2], "queue_direction": "-x"}]
+        }"#,
+    )
+    .unwrap();
+    let state = ModelState::new((10., 10.), 10, 100, None, scenario);
+
+    assert_eq!(state.services.len(), 1);
+    assert_eq!(state.service_states.len(), 1);
+    //Two places fit in front of the short one before the edge of the grid
+    let last = state.services[0].queue_slot(5);
+    assert_eq!((last.x, last.y), (0., 2.));
+}
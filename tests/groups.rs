@@ -0,0 +1,71 @@
+use ndarray::Array2;
+use pedestrian_sim::{ModelState, Scenario, Simulation};
+
+#[test]
+fn group_members_move_like_everybody_else() {
+    let scenario: Scenario = serde_json::from_str(&format!(
+        r#"{{
+            "output_dir": "{}",
+            "seed": 5,
+            "groups": {{"size_shares": [0, 0, 1], "formation": "v_shape"}}
+        }}"#,
+        std::env::temp_dir()
+            .join("pedestrian_sim_groups")
+            .to_string_lossy()
+            .replace('\\', "/")
+    ))
+    .unwrap();
+    let state = ModelState::new((30., 30.), 12, 200, None, scenario);
+    assert!(!state.groups.is_empty());
+
+    let mut simulation = Simulation::new(state);
+    while simulation.step() {
+        //Every pedestrian walks one cell per step, along a row or a column
+        for ped in simulation.state.active_peds.values() {
+            assert!(
+                ped.last_d.x.abs() + ped.last_d.y.abs() <= 1.,
+                "pedestrian {} moved by {:?}",
+                ped.id,
+                (ped.last_d.x, ped.last_d.y)
+            );
+        }
+    }
+}
+
+#[test]
+fn group_members_catching_up_keep_out_of_walls() {
+    let scenario: Scenario = serde_json::from_str(&format!(
+        r#"{{
+            "output_dir": "{}",
+            "seed": 8,
+            "groups": {{"size_shares": [0, 0, 1], "formation": "side_by_side"}}
+        }}"#,
+        std::env::temp_dir()
+            .join("pedestrian_sim_groups_walls")
+            .to_string_lossy()
+            .replace('\\', "/")
+    ))
+    .unwrap();
+    //Walled down column 15, open only in the top and bottom three rows
+    let raster = Array2::from_shape_fn((30, 30), |(row, col)| {
+        match col == 15 && (3..27).contains(&row) {
+            true => 0,
+            false => 255,
+        }
+    });
+    let state = ModelState::new((30., 30.), 24, 300, Some(raster.clone()), scenario);
+    assert!(!state.groups.is_empty());
+
+    let mut simulation = Simulation::new(state);
+    while simulation.step() {
+        for ped in simulation.state.active_peds.values() {
+            assert_ne!(
+                raster[[ped.loc.y as usize, ped.loc.x as usize]],
+                0,
+                "pedestrian {} is inside the wall at step {}",
+                ped.id,
+                simulation.state.step
+            );
+        }
+    }
+}
@@ -0,0 +1,60 @@
+use krabmaga::engine::location::Int2D;
+use ndarray::Array2;
+use pedestrian_sim::{ModelState, Scenario, Simulation};
+
+//A 30 x 30 raster walled down column 15, open only in the top and bottom three rows
+fn walled_raster() -> Array2<u8> {
+    Array2::from_shape_fn((30, 30), |(row, col)| {
+        match col == 15 && (3..27).contains(&row) {
+            true => 0,
+            false => 255,
+        }
+    })
+}
+
+fn in_wall(raster: &Array2<u8>, state: &ModelState) -> Vec<u32> {
+    state
+        .active_peds
+        .values()
+        .filter(|ped| raster[[ped.loc.y as usize, ped.loc.x as usize]] == 0)
+        .map(|ped| ped.id)
+        .collect()
+}
+
+#[test]
+fn pedestrians_replanned_around_a_closure_keep_out_of_walls() {
+    let scenario: Scenario = serde_json::from_str(&format!(
+        r#"{{
+            "output_dir": "{}",
+            "seed": 3,
+            "obstacle_events": [{{"region": {{"x_min": 15, "y_min": 0, "x_max": 15, "y_max": 2}},
+                                  "start_step": 1, "end_step": 80}}]
+        }}"#,
+        std::env::temp_dir()
+            .join("pedestrian_sim_obstacles")
+            .to_string_lossy()
+            .replace('\\', "/")
+    ))
+    .unwrap();
+    let raster = walled_raster();
+    let state = ModelState::new((30., 30.), 30, 300, Some(raster.clone()), scenario);
+
+    let mut simulation = Simulation::new(state);
+    while simulation.step() {
+        let state = &simulation.state;
+        assert!(
+            state.obj_grid.get_value(&Int2D { x: 15, y: 10 }).is_some(),
+            "the wall is gone at step {}",
+            state.step
+        );
+        assert!(
+            in_wall(&raster, state).is_empty(),
+            "pedestrians {:?} are inside the wall at step {}",
+            in_wall(&raster, state),
+            state.step
+        );
+    }
+    let state = simulation.into_state();
+    assert!(state.trips.values().any(|trip| trip.replans > 0));
+    assert!(state.obj_grid.get_value(&Int2D { x: 15, y: 1 }).is_none());
+}
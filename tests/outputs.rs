@@ -0,0 +1,132 @@
+use image::Rgb;
+use ndarray::Array2;
+use pedestrian_sim::{
+    model::scenario::FrameOutput, system_interface::raster_writer::write_geotiff, ModelState,
+    Scenario, Simulation,
+};
+use tiff::{decoder::Decoder, tags::Tag};
+
+//A 20 x 20 raster walled down column 10, open only in the top and bottom two rows
+fn walled_raster() -> Array2<u8> {
+    Array2::from_shape_fn((20, 20), |(row, col)| {
+        match col == 10 && (2..18).contains(&row) {
+            true => 0,
+            false => 255,
+        }
+    })
+}
+
+fn walled_simulation(name: &str, outputs: &str) -> Simulation {
+    let scenario: Scenario = serde_json::from_str(&format!(
+        r#"{{"output_dir": "{}", "seed": 2 {}}}"#,
+        std::env::temp_dir()
+            .join(format!("pedestrian_sim_outputs_{}", name))
+            .to_string_lossy()
+            .replace('\\', "/"),
+        outputs
+    ))
+    .unwrap();
+    let state = ModelState::new((20., 20.), 10, 50, Some(walled_raster()), scenario);
+    Simulation::new(state)
+}
+
+#[test]
+fn frames_draw_the_walls() {
+    let mut simulation = walled_simulation("frames", "");
+    simulation.step();
+    let output: FrameOutput = serde_json::from_str(r#"{"scale": 1}"#).unwrap();
+    let frame = simulation.state.render_frame(&output);
+    let walls = frame
+        .pixels()
+        .filter(|pixel| **pixel == Rgb([0, 0, 0]))
+        .count();
+    assert_eq!(walls, 16);
+}
+
+#[test]
+fn density_maps_mask_the_walls() {
+    let mut simulation = walled_simulation(
+        "density",
+        r#", "density": {"interval": 1, "geotiff": false}"#,
+    );
+    simulation.run();
+    let path = std::path::Path::new(&simulation.state.output_dir).join("density_0_mean.png");
+    let map = image::open(path).unwrap().to_rgb8();
+    let walls = map
+        .pixels()
+        .filter(|pixel| **pixel == Rgb([0, 0, 0]))
+        .count();
+    assert_eq!(walls, 16);
+}
+
+#[test]
+fn density_windows_end_between_samples() {
+    let mut simulation = walled_simulation(
+        "density_windows",
+        r#", "density": {"interval": 3, "window": 5, "png": false, "geotiff": false}"#,
+    );
+    simulation.run();
+    let starts: Vec<u64> = simulation
+        .state
+        .density_windows
+        .iter()
+        .map(|(start, _)| *start)
+        .collect();
+    assert_eq!(starts, (0..50).step_by(5).collect::<Vec<u64>>());
+}
+
+#[test]
+fn geotiffs_carry_geokeys_and_grow_down_the_rows() {
+    let path = std::env::temp_dir().join("pedestrian_sim_outputs_geotiff.tif");
+    write_geotiff(&path, &Array2::zeros((4, 6)), 0.5).unwrap();
+
+    let mut decoder = Decoder::new(std::fs::File::open(&path).unwrap()).unwrap();
+    let scale = decoder.get_tag_f64_vec(Tag::ModelPixelScaleTag).unwrap();
+    assert_eq!(scale, vec![0.5, -0.5, 0.]);
+    let geo_keys = decoder.get_tag_u16_vec(Tag::GeoKeyDirectoryTag).unwrap();
+    assert_eq!(geo_keys[..4], [1, 1, 0, 4]);
+    assert!(geo_keys.chunks(4).any(|key| key == [1024, 0, 1, 1]));
+}
+
+#[test]
+fn los_outputs_leave_out_the_walls() {
+    let mut simulation = walled_simulation("los", r#", "los": {"interval": 1}"#);
+    simulation.run();
+    let output_dir = std::path::Path::new(&simulation.state.output_dir);
+
+    let cells = std::fs::read_to_string(output_dir.join("los_0_cells.csv")).unwrap();
+    assert_eq!(cells.lines().count(), 1 + 400 - 16);
+    let map = image::open(output_dir.join("los_0_map.png"))
+        .unwrap()
+        .to_rgb8();
+    let walls = map
+        .pixels()
+        .filter(|pixel| **pixel == Rgb([0, 0, 0]))
+        .count();
+    assert_eq!(walls, 16);
+}
+
+#[test]
+fn fundamental_diagram_areas_leave_out_the_walls() {
+    //Around the top gap: 5 x 11 cells, 9 of them wall
+    let mut simulation = walled_simulation(
+        "fd",
+        r#", "fundamental_diagram": {"areas": [{"name": "gap",
+              "region": {"x_min": 8, "y_min": 0, "x_max": 12, "y_max": 10}}],
+              "methods": ["C"], "png": false}"#,
+    );
+    simulation.run();
+    let state = simulation.state;
+    let cell_area = state.cell_size * state.cell_size;
+
+    assert!(!state.fd_samples.is_empty());
+    for sample in state.fd_samples.iter() {
+        let count = sample.density * 46. * cell_area;
+        assert!(
+            (count - count.round()).abs() < 1e-3 && count.round() >= 1.,
+            "density {} at step {} is no whole number of people on 46 cells",
+            sample.density,
+            sample.step
+        );
+    }
+}
@@ -0,0 +1,114 @@
+use ndarray::Array2;
+use pedestrian_sim::{model::scenario::PopulationMode, ModelState, Scenario, Simulation};
+
+fn scenario(name: &str, population: PopulationMode) -> Scenario {
+    Scenario {
+        output_dir: std::env::temp_dir()
+            .join(format!("pedestrian_sim_{}", name))
+            .to_string_lossy()
+            .into_owned(),
+        seed: Some(7),
+        population,
+        ..Default::default()
+    }
+}
+
+//Run the state like simulate! does: init, then step until the end condition, for every repetition
+fn run_repetitions(mut state: ModelState, repetitions: usize) -> ModelState {
+    for _ in 0..repetitions {
+        let mut simulation = Simulation::new(state);
+        simulation.run();
+        state = simulation.into_state();
+    }
+    state
+}
+
+#[test]
+fn later_runs_start_from_a_fresh_population() {
+    let state = ModelState::new(
+        (20., 20.),
+        10,
+        200,
+        None,
+        scenario("resample", PopulationMode::Resample),
+    );
+    let state = run_repetitions(state, 2);
+
+    assert_eq!(state.trip_summaries.len(), 2);
+    for summary in state.trip_summaries.iter() {
+        assert!(summary.trips > 0, "run {} has no trips", summary.run);
+        assert!(summary.arrived > 0, "run {} has no arrivals", summary.run);
+        assert!(summary.mean_planned_length > 0.);
+        assert!(summary.mean_travel_time > 0.);
+    }
+}
+
+#[test]
+fn replayed_runs_repeat_the_first() {
+    let state = ModelState::new(
+        (20., 20.),
+        10,
+        200,
+        None,
+        scenario("replay", PopulationMode::Replay),
+    );
+    let state = run_repetitions(state, 2);
+
+    let (first, second) = (&state.trip_summaries[0], &state.trip_summaries[1]);
+    assert!(second.mean_travel_time > 0.);
+    assert_eq!(first.trips, second.trips);
+    assert_eq!(first.arrived, second.arrived);
+    assert_eq!(first.mean_travel_time, second.mean_travel_time);
+    assert_eq!(first.mean_walked_length, second.mean_walked_length);
+}
+
+#[test]
+fn resampled_runs_keep_out_of_walls() {
+    //Walled down column 10, open only in the top and bottom two rows
+    let raster = Array2::from_shape_fn((20, 20), |(row, col)| {
+        match col == 10 && (2..18).contains(&row) {
+            true => 0,
+            false => 255,
+        }
+    });
+    let in_wall = |x: f32, y: f32| raster[[y as usize, x as usize]] == 0;
+
+    let mut state = ModelState::new(
+        (20., 20.),
+        40,
+        150,
+        Some(raster.clone()),
+        scenario("resample_walls", PopulationMode::Resample),
+    );
+    for _ in 0..2 {
+        let mut simulation = Simulation::new(state);
+        let run = simulation.state.run;
+        for ped in simulation.state.peds.iter() {
+            assert!(
+                !in_wall(ped.loc.x, ped.loc.y),
+                "run {} starts {} in a wall",
+                run,
+                ped.id
+            );
+            let path = simulation.state.ped_paths[&ped.id].as_slice();
+            assert!(
+                !path.iter().any(|point| in_wall(point.x, point.y)),
+                "run {} plans {} through a wall",
+                run,
+                ped.id
+            );
+        }
+        while simulation.step() {
+            for ped in simulation.state.active_peds.values() {
+                assert!(
+                    !in_wall(ped.loc.x, ped.loc.y),
+                    "run {} walks {} into a wall",
+                    run,
+                    ped.id
+                );
+            }
+        }
+        state = simulation.into_state();
+    }
+    assert_eq!(state.trip_summaries.len(), 2);
+}
@@ -0,0 +1,58 @@
+use krabmaga::engine::location::Int2D;
+use ndarray::Array2;
+use pedestrian_sim::{ModelState, Scenario, Simulation};
+
+fn scenario() -> Scenario {
+    serde_json::from_str(&format!(
+        r#"{{
+            "output_dir": "{}",
+            "seed": 9,
+            "obstacle_events": [{{"region": {{"x_min": 15, "y_min": 0, "x_max": 15, "y_max": 2}},
+                                  "start_step": 1}}]
+        }}"#,
+        std::env::temp_dir()
+            .join("pedestrian_sim_snapshots")
+            .to_string_lossy()
+            .replace('\\', "/")
+    ))
+    .unwrap()
+}
+
+#[test]
+fn restored_runs_keep_the_walls_and_closures() {
+    //Walled down column 15, open only in the top and bottom three rows
+    let raster = Array2::from_shape_fn((30, 30), |(row, col)| {
+        match col == 15 && (3..27).contains(&row) {
+            true => 0,
+            false => 255,
+        }
+    });
+    let state = ModelState::new((30., 30.), 20, 200, Some(raster.clone()), scenario());
+    let mut simulation = Simulation::new(state);
+    for _ in 0..20 {
+        simulation.step();
+    }
+    let snapshot = simulation.state.take_snapshot();
+    assert_eq!(snapshot.obstacles.len(), 24);
+    assert_eq!(snapshot.applied_closures.len(), 1);
+
+    //The snapshot carries the raster, so it is restored onto an open grid of the same size
+    let mut state = ModelState::new((30., 30.), 20, 200, None, scenario());
+    state.restore_snapshot(snapshot).unwrap();
+    let mut simulation = Simulation::new(state);
+    let obj_grid = &simulation.state.obj_grid;
+    assert!(obj_grid.get_value(&Int2D { x: 15, y: 10 }).is_some());
+    assert!(obj_grid.get_value(&Int2D { x: 15, y: 1 }).is_some());
+
+    while simulation.step() {
+        for ped in simulation.state.active_peds.values() {
+            assert_ne!(
+                raster[[ped.loc.y as usize, ped.loc.x as usize]],
+                0,
+                "pedestrian {} is inside the wall at step {}",
+                ped.id,
+                simulation.state.step
+            );
+        }
+    }
+}
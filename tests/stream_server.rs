@@ -0,0 +1,29 @@
+use pedestrian_sim::system_interface::stream_server::StreamServer;
+use std::net::TcpStream;
+use std::thread;
+use std::time::{Duration, Instant};
+
+#[test]
+fn a_silent_connection_does_not_hold_up_other_viewers() {
+    let address = "127.0.0.1:19317";
+    let server = StreamServer::bind(address).unwrap();
+
+    //Connects but never sends the WebSocket handshake
+    let _silent = TcpStream::connect(address).unwrap();
+    thread::sleep(Duration::from_millis(100));
+    let stream = TcpStream::connect(address).unwrap();
+    stream
+        .set_read_timeout(Some(Duration::from_secs(2)))
+        .unwrap();
+    let (_viewer, _) = tungstenite::client(format!("ws://{}", address), stream)
+        .expect("the viewer was not answered");
+
+    let start = Instant::now();
+    while !server.has_clients() {
+        assert!(
+            start.elapsed() < Duration::from_secs(2),
+            "the viewer was not accepted"
+        );
+        thread::sleep(Duration::from_millis(10));
+    }
+}